@@ -1,5 +1,6 @@
 use structopt::StructOpt;
 use std::path::PathBuf;
+use crate::placement::Place;
 
 #[derive(Debug, StructOpt)]
 pub enum Args {
@@ -7,29 +8,147 @@ pub enum Args {
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    Recover(RecoverArgs),
+    Validate(ValidateArgs),
+    Trailer(TrailerArgs),
+    Diff(DiffArgs),
+    Text(TextArgs),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct EncodeArgs {
     pub file_path: PathBuf,
-    pub chunk_type: String,
+    pub chunk_type: String, // `auto` for a random private name
     pub message: String,
-    pub output_file: Option<PathBuf> // Option-al
+    pub output_file: Option<PathBuf>, // Option-al
+    #[structopt(long)]
+    pub type_from_key: bool, // The chunk type argument is a passphrase to derive the name from
+    #[structopt(long)]
+    pub raw: bool, // Keep damaged and non-conforming chunks byte for byte
+    #[structopt(long)]
+    pub backup: bool, // Keep the original as `<file>.bak` when overwriting it
+    #[structopt(long)]
+    pub touch: bool, // Set the tIME chunk to now
+    #[structopt(long, conflicts_with_all = &["output-file", "backup", "raw", "touch"])]
+    pub in_place: bool, // Only rewrite the file from the new chunk on (fast on huge files, but not crash-safe)
+    #[structopt(long, default_value = "before-iend")]
+    pub place: Place, // before-iend, after-ihdr, before-idat or after-iend
 }
 
 #[derive(Debug, StructOpt)]
 pub struct DecodeArgs {
-    pub file_path: PathBuf,
-    pub chunk_type: String,
+    pub file_path: PathBuf, // `-` for stdin
+    pub chunk_type: String, // `auto` for any non-standard chunk
+    #[structopt(long)]
+    pub type_from_key: bool, // The chunk type argument is a passphrase to derive the name from
+    #[structopt(long)]
+    pub index: Option<usize>, // Which chunk of that type (0 is the first one, the default)
+    #[structopt(long, conflicts_with = "index")]
+    pub all: bool, // Every chunk of that type
 }
 
 #[derive(Debug, StructOpt)]
 pub struct RemoveArgs {
     pub file_path: PathBuf,
     pub chunk_type: String,
+    pub output_file: Option<PathBuf>, // Option-al
+    #[structopt(long)]
+    pub index: Option<usize>, // Which chunk of that type (0 is the first one, the default)
+    #[structopt(long, conflicts_with = "index")]
+    pub all: bool, // Every chunk of that type
+    #[structopt(long)]
+    pub raw: bool, // Keep damaged and non-conforming chunks byte for byte
+    #[structopt(long)]
+    pub backup: bool, // Keep the original as `<file>.bak` when overwriting it
+    #[structopt(long)]
+    pub touch: bool, // Set the tIME chunk to now
 }
 
 #[derive(Debug, StructOpt)]
 pub struct PrintArgs {
+    pub file_path: PathBuf, // `-` for stdin
+    #[structopt(long)]
+    pub palette: bool, // Also show the palette entries as RGBA
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RecoverArgs {
+    pub file_path: PathBuf,
+    pub output_file: PathBuf, // Never overwrite the damaged original
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ValidateArgs {
     pub file_path: PathBuf,
 }
+
+#[derive(Debug, StructOpt)]
+pub struct DiffArgs {
+    pub old_file: PathBuf,
+    pub new_file: PathBuf,
+    #[structopt(long)]
+    pub porcelain: bool, // One tab separated line per change, for scripts
+}
+
+/// Raw bytes following IEND
+#[derive(Debug, StructOpt)]
+pub enum TrailerArgs {
+    Extract {
+        file_path: PathBuf,
+        output_file: PathBuf,
+    },
+    Strip {
+        file_path: PathBuf,
+        output_file: Option<PathBuf>, // Option-al
+        #[structopt(long)]
+        touch: bool, // Set the tIME chunk to now
+    },
+    Set {
+        file_path: PathBuf,
+        data_file: PathBuf, // Bytes to put after IEND
+        output_file: Option<PathBuf>, // Option-al
+        #[structopt(long)]
+        touch: bool, // Set the tIME chunk to now
+    },
+}
+
+/// Standard textual metadata (tEXt, zTXt and iTXt chunks)
+#[derive(Debug, StructOpt)]
+pub enum TextArgs {
+    Set {
+        file_path: PathBuf,
+        keyword: String, // Title, Author, Description, Copyright, Comment...
+        text: String,
+        output_file: Option<PathBuf>, // Option-al
+        #[structopt(long)]
+        language: Option<String>, // Language tag such as `fr` or `en-GB`, stored as iTXt (also used for non Latin-1 text)
+        #[structopt(long)]
+        translated_keyword: Option<String>, // Keyword in that language, stored as iTXt
+        #[structopt(long)]
+        compress: bool, // Store the text zlib compressed (zTXt, or compressed iTXt)
+        #[structopt(long)]
+        backup: bool, // Keep the original as `<file>.bak` when overwriting it
+        #[structopt(long)]
+        touch: bool, // Set the tIME chunk to now
+    },
+    Get {
+        file_path: PathBuf, // `-` for stdin
+        keyword: String,
+        #[structopt(long)]
+        language: Option<String>, // Only this language tag (`""` for tEXt and zTXt)
+    },
+    List {
+        file_path: PathBuf, // `-` for stdin
+    },
+    Remove {
+        file_path: PathBuf,
+        keyword: String,
+        output_file: Option<PathBuf>, // Option-al
+        #[structopt(long)]
+        language: Option<String>, // Only this language tag (`""` for tEXt and zTXt)
+        #[structopt(long)]
+        backup: bool, // Keep the original as `<file>.bak` when overwriting it
+        #[structopt(long)]
+        touch: bool, // Set the tIME chunk to now
+    },
+}
@@ -1,15 +1,16 @@
 use crate::chunk_type::ChunkType;
 use crate::utils::{Error, Result};
-use crc;
 use std::fmt::Display;
+use std::ops::Deref;
 use std::str::from_utf8;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
     chunk_type: ChunkType, // Type of a chunk (4-bytes code)
     data: Vec<u8>,         // Data bytes
                            // Length and CRC don't have to be part of the type, and are exterior data which can be computed
                            // This lightens the type definition
+    stored_crc: Option<u32>, // CRC as read (possibly wrong), written back as is; `None` for created chunks
 }
 
 impl Chunk {
@@ -19,12 +20,19 @@ impl Chunk {
     pub const CRC_BYTES: usize = 4; // Number of crc bytes
                                     // Data bytes number is omitted as infered by the actual number that `data_len` represents
 
+    /// Largest data length allowed by the spec (2^31-1)
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
+
     /// Data len, type and CRC bytes
     pub const TOTAL_BYTES: usize = Self::DATA_LEN_BYTES + Self::CHUNK_TYPE_BYTES + Self::CRC_BYTES;
     // Example of u8 slice for constructing a Chunk: [0, 0, 0, 1(length), 97, 97, 97, 97(c_t), ]
 
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        Self { chunk_type, data }
+        Self {
+            chunk_type,
+            data,
+            stored_crc: None,
+        }
     }
     pub fn length(&self) -> u32 {
         self.data().len() as u32
@@ -35,28 +43,43 @@ impl Chunk {
     pub fn data(&self) -> &[u8] {
         self.data.as_slice() // Complete slice of the inner data vector
     }
+    /// CRC computed from the content
     pub fn crc(&self) -> u32 {
-        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-        let mut c_type_bytes = self.chunk_type().bytes().to_vec();
-        let mut data_bytes: Vec<u8> = self.data().try_into().unwrap();
-        c_type_bytes.append(&mut data_bytes);
-        crc.checksum(&c_type_bytes)
+        crc_of(self.chunk_type(), self.data())
+    }
+    /// CRC as it was read, if the chunk was parsed
+    pub fn stored_crc(&self) -> Option<u32> {
+        self.stored_crc
+    }
+    /// CRC written on output: the stored one if any (so damaged chunks round-trip exactly), else the computed one
+    pub fn output_crc(&self) -> u32 {
+        self.stored_crc.unwrap_or_else(|| self.crc())
     }
+    /// Does the stored CRC (if any) match the content?
+    pub fn has_valid_crc(&self) -> bool {
+        self.stored_crc.is_none_or(|crc| crc == self.crc())
+    }
+    /// Whole data as UTF-8, as hidden messages are stored (text chunks are decoded by the `text` module)
     pub fn data_as_string(&self) -> Result<String> {
         Ok(from_utf8(self.data()).map_err(|_| Error::InvalidChar)?.to_string())
     }
+    /// Replaces the data, dropping the stored CRC (an edited chunk always gets a fresh one)
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+        self.stored_crc = None;
+    }
     /// Chunk as a sequence of bytes (same infos order as for building from bytes)
     pub fn as_bytes(&self) -> Vec<u8> {
         let length_bytes = self.length().to_be_bytes();
         let c_type_bytes = self.chunk_type().bytes();
-        let data_bytes: Vec<u8> = self.data().try_into().unwrap();
-        let crc_bytes = self.crc().to_be_bytes();
+        let data_bytes: Vec<u8> = self.data().to_vec();
+        let crc_bytes = self.output_crc().to_be_bytes();
 
         length_bytes
             .into_iter()
-            .chain(c_type_bytes.into_iter())
-            .chain(data_bytes.into_iter())
-            .chain(crc_bytes.into_iter())
+            .chain(c_type_bytes)
+            .chain(data_bytes)
+            .chain(crc_bytes)
             .collect()
     }
 }
@@ -66,62 +89,181 @@ impl TryFrom<&[u8]> for Chunk {
 
     /// Takes a sequence of bytes, and transform it into a data length (4 bytes), a type (4 bytes), some data (of length `length` (radix 10 representation)) and the crc (which is just checked, not taken as is)
     fn try_from(bytes: &[u8]) -> std::result::Result<Self, Self::Error> {
+        ChunkRef::try_from(bytes).map(Chunk::from) // Parsed as a borrowed view, then copied
+    }
+}
+
+/// Mutable access to a chunk which can edit its data but not its type, so that chunk placement rules cannot be broken through it
+#[derive(Debug)]
+pub struct ChunkMut<'a> {
+    chunk: &'a mut Chunk,
+}
+
+impl<'a> ChunkMut<'a> {
+    pub fn new(chunk: &'a mut Chunk) -> Self {
+        Self { chunk }
+    }
+    /// See `Chunk::set_data`
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.chunk.set_data(data);
+    }
+}
+
+impl Deref for ChunkMut<'_> {
+    type Target = Chunk;
+
+    fn deref(&self) -> &Chunk {
+        self.chunk
+    }
+}
+
+/// Type code, followed by its name for standard chunks
+fn described(chunk_type: &ChunkType) -> String {
+    match chunk_type.known() {
+        Some(known) => format!("{chunk_type} ({})", known.description()),
+        None => chunk_type.to_string(),
+    }
+}
+
+/// CRC of a chunk, computed over its type and data bytes (length is excluded)
+pub fn crc_of(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+    let mut digest = crc.digest();
+    digest.update(&chunk_type.bytes());
+    digest.update(data);
+    digest.finalize()
+}
+
+/// Borrowed counterpart of `Chunk`, whose data points into the parsed input instead of being copied
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32, // Stored CRC, checked against the content at creation
+}
+
+impl<'a> ChunkRef<'a> {
+    /// Forensic alternative of `try_from`: only the layout is checked, the type bytes and stored CRC are kept whatever they are
+    pub fn from_raw(bytes: &'a [u8]) -> Result<Self> {
         // First check
-        if bytes.len() < Self::TOTAL_BYTES {
+        if bytes.len() < Chunk::TOTAL_BYTES {
             // Minimum length of a construct slice (data may be empty)
             return Err(Error::ChunkSliceSizeError);
         }
 
         // Data length
-        let (data_length, remaining) = bytes.split_at(Self::DATA_LEN_BYTES); // This consumes a certain amount of bytes of the total slice from the head
+        let (data_length, remaining) = bytes.split_at(Chunk::DATA_LEN_BYTES); // This consumes a certain amount of bytes of the total slice from the head
         let data_length =
             u32::from_be_bytes(data_length.try_into().unwrap() /*&[u8] -> [u8; 4]*/);
 
         // Second check (is data the actual correct size represented by `data_len`? if not slice is rejected)
-        if remaining.len() != Self::CHUNK_TYPE_BYTES + Self::CRC_BYTES + data_length as usize
+        if remaining.len() as u64 != (Chunk::CHUNK_TYPE_BYTES + Chunk::CRC_BYTES) as u64 + data_length as u64
         /*Data has already been consumed from the slice*/
         {
             return Err(Error::ChunkSliceSizeError);
         }
 
         // Chunk type
-        let (c_type, remaining) = remaining.split_at(Self::CHUNK_TYPE_BYTES);
-        let c_type = ChunkType::try_from(
-            <[u8; 4]>::try_from(c_type).unwrap(), /*&[u8] -> [u8; 4]*/
-        )
-        .unwrap();
+        let (c_type, remaining) = remaining.split_at(Chunk::CHUNK_TYPE_BYTES);
+        let c_type = ChunkType::from_raw(<[u8; 4]>::try_from(c_type).unwrap() /*&[u8] -> [u8; 4], length checked above*/);
 
         // Data
         let (data, remaining) = remaining.split_at(data_length as usize); // If length is zero this does nothing
-        let data: Vec<u8> = data.try_into().unwrap(); // &[u8] -> Vec<u8>
 
         // CRC
         let crc = u32::from_be_bytes(
-            remaining.split_at(Self::CRC_BYTES).0.try_into().unwrap(), /*&[u8] -> [u8; 4]*/
+            remaining.split_at(Chunk::CRC_BYTES).0.try_into().unwrap(), /*&[u8] -> [u8; 4]*/
         );
 
-        let chunk = Chunk::new(c_type, data);
+        Ok(Self {
+            chunk_type: c_type,
+            data,
+            crc,
+        })
+    }
+
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+    /// CRC as stored in the input (checked against the content, unless built with `from_raw`)
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+    pub fn data_as_string(&self) -> Result<String> {
+        Ok(from_utf8(self.data).map_err(|_| Error::InvalidChar)?.to_string())
+    }
+    /// Total bytes the chunk takes in its input (length, type, data and CRC)
+    pub fn size(&self) -> usize {
+        self.data.len() + Chunk::TOTAL_BYTES
+    }
+    /// Owned copy, for editing
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::from(*self)
+    }
+}
+
+impl From<ChunkRef<'_>> for Chunk {
+    fn from(chunk: ChunkRef<'_>) -> Self {
+        Self {
+            chunk_type: chunk.chunk_type,
+            data: chunk.data.to_vec(),
+            stored_crc: Some(chunk.crc),
+        }
+    }
+}
+
+/// View of an owned chunk, with the CRC it is written with (the stored one, if it was read)
+impl<'a> From<&'a Chunk> for ChunkRef<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        Self {
+            chunk_type: chunk.chunk_type,
+            data: &chunk.data,
+            crc: chunk.output_crc(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = Error;
+
+    /// Same layout and checks as for `Chunk`, without copying the data
+    fn try_from(bytes: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        let chunk = Self::from_raw(bytes)?;
+        ChunkType::try_from(chunk.chunk_type.bytes())?; // Only ASCII letters
 
         //Is the crc valid?
-        if chunk.crc() == crc {
-            Ok(chunk) // We have here a valid and from bytes built Chunk
+        if crc_of(&chunk.chunk_type, chunk.data) == chunk.crc {
+            Ok(chunk) // We have here a valid view over the bytes
         } else {
             Err(Error::CRC) // If not the creation has to be aborted
         }
     }
 }
 
-impl Display for Chunk {
+impl Display for ChunkRef<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(
             f,
             "{}, {}, [some lengthy data sequence], {}",
             self.length(),
-            self.chunk_type(),
+            described(self.chunk_type()),
             self.crc()
         )
     }
 }
+
+/// Same as its `ChunkRef` view, so with the CRC found in the file
+impl Display for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ChunkRef::from(self).fmt(f)
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,4 +412,45 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_chunk_ref_borrows_input() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        let chunk_ref = ChunkRef::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(chunk_ref.data().as_ptr(), bytes[8..].as_ptr()); // No copy
+        assert_eq!(chunk_ref.length(), 42);
+        assert_eq!(chunk_ref.crc(), 2882656334);
+        assert_eq!(chunk_ref.to_chunk().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_set_data_refreshes_crc() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF; // Bad CRC
+        let mut chunk = ChunkRef::from_raw(bytes.as_slice()).unwrap().to_chunk();
+
+        chunk.set_data(b"Edited".to_vec());
+        assert!(chunk.has_valid_crc());
+        assert_eq!(chunk.stored_crc(), None);
+        assert_eq!(Chunk::try_from(chunk.as_bytes().as_slice()).unwrap().data(), b"Edited");
+    }
+
+    #[test]
+    fn test_raw_chunk_round_trip() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes[5] = b'1'; // Non-conforming type
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF; // Bad CRC
+
+        assert!(Chunk::try_from(bytes.as_slice()).is_err());
+        let chunk = ChunkRef::from_raw(bytes.as_slice()).unwrap().to_chunk();
+        assert!(!chunk.has_valid_crc());
+        assert_eq!(chunk.as_bytes(), bytes);
+        let stored = u32::from_be_bytes(bytes[last - 3..].try_into().unwrap());
+        assert!(chunk.to_string().ends_with(&format!(", {stored}\n"))); // As in the file, not recomputed
+        assert_eq!(chunk.to_string(), ChunkRef::from_raw(bytes.as_slice()).unwrap().to_string());
+    }
 }
@@ -1,22 +1,25 @@
 use crate::Error;
 use std::{
+    collections::hash_map::RandomState,
     fmt::Display,
+    hash::{BuildHasher, Hasher},
     str::{self, from_utf8, FromStr},
+    time::SystemTime,
 };
 
 /// The representation of a chunk type (containing raw bytes of the chunk code)
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
 
 /// Ability to build a ChunkType instance from [u8; 4]
+/// Any 4 ASCII letters are accepted, so that chunks read from real-world files never get rejected for their reserved bit
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
     fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-        let a = Self {bytes};
-        if a.is_valid() {
-            Ok(a)
+        if bytes.iter().all(|b| b.is_ascii_alphabetic()) {
+            Ok(Self { bytes })
         } else {
             Err(Error::InvalidType)
         }
@@ -42,17 +45,44 @@ impl FromStr for ChunkType {
             return Err(Error::InvalidChar);
         }
 
-        Ok(ChunkType::try_from(<[u8; 4]>::try_from(bytes_s).unwrap())?) // Actual return is an array of bytes wrapped in ChunkType
+        ChunkType::try_from(<[u8; 4]>::try_from(bytes_s).unwrap()) // Actual return is an array of bytes wrapped in ChunkType
     }
 }
 
 impl Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", from_utf8(&self.bytes).unwrap())
+        write!(f, "{}", String::from_utf8_lossy(&self.bytes)) // Lossy, as raw types may hold any byte
+    }
+}
+
+/// Comparison with a type code, on raw bytes (no allocation)
+impl PartialEq<str> for ChunkType {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes[..] == *other.as_bytes()
     }
 }
 
 impl ChunkType {
+    /// Unchecked constructor, keeping any bytes as read (forensic parsing only)
+    pub(crate) fn from_raw(bytes: [u8; 4]) -> Self {
+        Self { bytes }
+    }
+
+    /// Builder of valid chunk type names from their property bits (see `ChunkTypeBuilder`)
+    pub fn builder() -> ChunkTypeBuilder {
+        ChunkTypeBuilder::default()
+    }
+
+    /// Strict alternative of `from_str`, also rejecting a set reserved bit (used for chunks the user creates)
+    pub fn from_str_strict(s: &str) -> Result<Self, Error> {
+        let chunk_type = Self::from_str(s)?;
+        if chunk_type.is_valid() {
+            Ok(chunk_type)
+        } else {
+            Err(Error::InvalidType)
+        }
+    }
+
     /// Internal raw bytes
     pub fn bytes(&self) -> [u8; 4] {
         self.bytes
@@ -60,9 +90,7 @@ impl ChunkType {
 
     /// Chunk validity check (only ASCII alphabetic bytes and is_reserved_bit_valid is true)
     pub fn is_valid(&self) -> bool {
-        let bytes = self.bytes(); // Avoid early dropping
-        let mut chars = from_utf8(&bytes).unwrap().chars(); //For cchars checking
-        let valid_chars = chars.all(|c| c.is_ascii_alphabetic());
+        let valid_chars = self.bytes().iter().all(|b| b.is_ascii_alphabetic()); // Checked on raw bytes, which may not be UTF-8
         valid_chars && self.is_reserved_bit_valid() // Checked chars and reserved bit is valid
     }
 
@@ -91,6 +119,94 @@ impl ChunkType {
     }
 }
 
+/// Picks chunk type names with the wanted property bits, so that users don't have to get the letter cases right
+/// Defaults to ancillary, private and safe-to-copy, the kind of chunk every decoder ignores and every editor keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkTypeBuilder {
+    critical: bool,
+    public: bool,
+    safe_to_copy: bool,
+}
+
+impl Default for ChunkTypeBuilder {
+    fn default() -> Self {
+        Self {
+            critical: false,
+            public: false,
+            safe_to_copy: true,
+        }
+    }
+}
+
+impl ChunkTypeBuilder {
+    pub fn critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    pub fn public(mut self, public: bool) -> Self {
+        self.public = public;
+        self
+    }
+
+    pub fn safe_to_copy(mut self, safe_to_copy: bool) -> Self {
+        self.safe_to_copy = safe_to_copy;
+        self
+    }
+
+    /// Applies the property bits to 4 letters (reserved bit always valid)
+    pub fn with_letters(&self, letters: [u8; 4]) -> Result<ChunkType, Error> {
+        let mut chunk_type = ChunkType::try_from(letters)?;
+        let cases = [!self.critical, !self.public, false, self.safe_to_copy]; // Lowercase bit of each byte
+        for (byte, lowercase) in chunk_type.bytes.iter_mut().zip(cases) {
+            *byte = if lowercase { byte.to_ascii_lowercase() } else { byte.to_ascii_uppercase() };
+        }
+        Ok(chunk_type)
+    }
+
+    /// Random name (not cryptographically random, only meant not to be the same every time)
+    pub fn random(&self) -> ChunkType {
+        let state = RandomState::new(); // Randomly keyed for each instance
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        self.generate(|attempt| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u32(attempt);
+            hasher.finish()
+        })
+    }
+
+    /// Name derived from a passphrase: the same passphrase always gives the same name, on any platform
+    pub fn keyed(&self, key: &str) -> ChunkType {
+        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC); // Stable, unlike std hashers
+        self.generate(|attempt| {
+            let mut digest = crc.digest();
+            digest.update(key.as_bytes());
+            digest.update(&attempt.to_be_bytes());
+            digest.finalize() as u64
+        })
+    }
+
+    /// Turns seeds into letters until the name is not a standard chunk one
+    fn generate(&self, seed: impl Fn(u32) -> u64) -> ChunkType {
+        (0..)
+            .map(|attempt| {
+                let mut seed = seed(attempt);
+                let letters = [0; 4].map(|_| {
+                    let letter = b'a' + (seed % 26) as u8;
+                    seed /= 26;
+                    letter
+                });
+                self.with_letters(letters).unwrap() // Always letters
+            })
+            .find(|chunk_type| chunk_type.known().is_none())
+            .unwrap() // At most a few attempts, as standard names are a tiny share of all names
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
     use std::convert::TryFrom;
@@ -175,6 +291,43 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_strict() {
+        assert!(ChunkType::from_str_strict("RuSt").is_ok());
+        assert!(ChunkType::from_str_strict("Rust").is_err());
+        assert!(ChunkType::try_from(*b"Rust").is_ok());
+        assert!(ChunkType::try_from([82, 117, 0xFF, 116]).is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_eq_str() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert!(chunk == *"RuSt");
+        assert!(chunk != *"Rust");
+        assert!(chunk != *"RuStt");
+    }
+
+    #[test]
+    pub fn test_chunk_type_builder() {
+        let builder = ChunkType::builder();
+        assert_eq!(builder.with_letters(*b"rust").unwrap(), ChunkType::from_str("ruSt").unwrap());
+        let builder = builder.critical(true).public(true).safe_to_copy(false);
+        assert_eq!(builder.with_letters(*b"rust").unwrap(), ChunkType::from_str("RUST").unwrap());
+        assert!(builder.with_letters(*b"ru5t").is_err());
+
+        let random = ChunkType::builder().random();
+        assert!(random.is_valid() && !random.is_critical() && !random.is_public() && random.is_safe_to_copy());
+        assert!(random.known().is_none());
+    }
+
+    #[test]
+    pub fn test_chunk_type_keyed() {
+        let builder = ChunkType::builder();
+        assert_eq!(builder.keyed("passphrase"), builder.keyed("passphrase"));
+        assert_ne!(builder.keyed("passphrase"), builder.keyed("another one"));
+        assert!(builder.keyed("passphrase").is_valid());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -1,63 +1,400 @@
-use crate::args::{DecodeArgs, EncodeArgs, PrintArgs, RemoveArgs};
+use crate::args::{
+    DecodeArgs, DiffArgs, EncodeArgs, PrintArgs, RecoverArgs, RemoveArgs, TextArgs, TrailerArgs, ValidateArgs,
+};
 use crate::chunk::Chunk;
 use crate::chunk_type::ChunkType;
-use crate::png::Png;
-use crate::utils::{Error, Result as R};
-use std::fs;
-use std::path::{PathBuf};
-use std::str::FromStr;
+use crate::editor::Editor;
+use crate::ihdr::Ihdr;
+use crate::in_place::insert_chunk_in_place;
+use crate::palette::{Palette, Transparency};
+use crate::png::{trailer_summary, ParseOptions, Png};
+use crate::reader::ChunkReader;
+use crate::text::{InternationalText, Text, TextChunk};
+use crate::time::ImageTime;
+use crate::utils::{ByteStats, Error, Result as R};
+use crate::validate::Severity;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::PathBuf;
 
 //type R<T> = Result<T, Box<dyn std::error::Error>>;
 
 /// Encoding command
 pub fn encode(args: EncodeArgs) -> R<()> {
-    let contents = contents(&args.file_path)?; // Png as bytes
-    let mut png = Png::try_from(contents.as_slice())?; // New png with exact same definition as opened one
-
     let message = args.message;
-    let chunk = Chunk::new(ChunkType::from_str(&args.chunk_type)?,message.as_bytes().to_vec());
-    png.append_chunk(chunk); // Encoding invisible messages really means putting the chunk after IEND 
-    let new_contents = png.as_bytes();
-    
-    let save_path = if let Some(path) = args.output_file {
-        path
-    } else {
-        args.file_path
-    }; // If specified, use given path, else overwrite
-    fs::write(save_path, new_contents).map_err(|_| Error::FileError)?; // New png file save
+    let chunk_type = match args.chunk_type.as_str() {
+        _ if args.type_from_key => ChunkType::builder().keyed(&args.chunk_type),
+        "auto" => {
+            let chunk_type = ChunkType::builder().random();
+            println!("Chunk type: {chunk_type}"); // Needed to decode the message back
+            chunk_type
+        }
+        code => ChunkType::from_str_strict(code)?, // Created chunks have to be fully valid
+    };
+    let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
+
+    if args.in_place {
+        // Only the bytes from the new chunk on get rewritten, without going through a temporary file
+        let mut file = File::options().read(true).write(true).open(&args.file_path).map_err(|_| Error::FileError)?;
+        insert_chunk_in_place(&mut file, &chunk, args.place)?;
+        file.sync_all().map_err(|_| Error::FileError)?;
+        return Ok(());
+    }
+
+    let options = ParseOptions { preserve_raw: args.raw, ..Default::default() };
+    let mut editor = Editor::open(args.file_path, &options)?.backup(args.backup); // New png with exact same definition as opened one
+    if let Some(path) = args.output_file {
+        editor = editor.output(path); // If specified, use given path, else overwrite
+    }
+    warn_reserved_bits(editor.png());
+    editor.insert_placed(chunk, args.place)?; // Before IEND by default, so that the message survives other tools
+    stamp_if(&mut editor, args.touch)?;
+    editor.commit()?; // New png file save
     Ok(())
 }
 
+/// Streams the chunks until the wanted one is found (so it works on stdin and huge files alike)
 pub fn decode(args: DecodeArgs) -> R<()> {
-    let png = Png::try_from(contents(&args.file_path)?.as_slice())?;
     let c_type = args.chunk_type;
-    let r_chunk = png.chunk_by_type(c_type.as_ref()).ok_or(Error::ChunkNotFound(c_type.to_string()))?;
-    let conv = r_chunk.data_as_string();
-    match conv {
-        Ok(message) => {
+    let keyed = args.type_from_key.then(|| ChunkType::builder().keyed(&c_type));
+    let auto = keyed.is_none() && c_type == "auto";
+    let is_wanted = |chunk_type: &ChunkType| match keyed {
+        Some(keyed) => *chunk_type == keyed,
+        None if auto => chunk_type.known().is_none(), // Whatever was hidden under a generated name
+        None => *chunk_type == *c_type,
+    };
+    let wanted = args.index.unwrap_or(0);
+    let mut found = 0; // Chunks of the wanted type met so far
+    for (c, chunk) in ChunkReader::new(input(&args.file_path)?)?.enumerate() {
+        let chunk = chunk?;
+        warn_reserved_bit(c, &chunk);
+        if !is_wanted(chunk.chunk_type()) {
+            continue;
+        }
+        if auto && std::str::from_utf8(chunk.data()).is_err() {
+            eprintln!("Warning: chunk {c} ({}) does not hold a message, skipped", chunk.chunk_type()); // Such as another tool's private chunk
+            continue;
+        }
+        found += 1;
+        if args.all {
+            let message = chunk.data_as_string()?;
+            println!("Found a message for the specified chunk type (chunk {c}): {}", message);
+        } else if found > wanted {
+            let message = chunk.data_as_string()?;
             println!("Found a message for the specified chunk type: {}", message);
-            Ok(())
+            return Ok(());
         }
-        _ => Err(Error::InvalidChar)
     }
-
+    if found == 0 || !args.all {
+        let name = keyed.map_or(c_type, |keyed| keyed.to_string()); // Never echo the passphrase
+        return Err(Error::ChunkNotFound(name));
+    }
+    Ok(())
 }
 
+/// Streams the chunks, printing them as they get read
 pub fn print(args: PrintArgs) -> R<()> {
-    println!("{}", Png::from_file(args.file_path).map_err(|_| Error::FileError)?);
+    let mut reader = ChunkReader::new(input(&args.file_path)?)?;
+    println!("Header: {:?}", Png::STANDARD_HEADER); // Checked by the reader
+    let mut violations = Vec::new();
+    let mut ihdr = None;
+    let (mut plte, mut trns) = (None, None); // Kept for `--palette`, decoded once the IHDR is known for sure
+    for (c, chunk) in reader.by_ref().enumerate() {
+        let chunk = chunk?;
+        println!("Chunk {c}: {chunk}");
+        if c == 0 && *chunk.chunk_type() == *"IHDR" {
+            match Ihdr::try_from(chunk.data()) {
+                Ok(decoded) => {
+                    println!("Image: {decoded}");
+                    ihdr = Some(decoded);
+                }
+                Err(e) => println!("Warning: {}", e.to_string().trim_end()),
+            }
+        }
+        if *chunk.chunk_type() == *"tIME" {
+            match ImageTime::try_from(chunk.data()) {
+                Ok(time) => println!("Last modified: {time}"),
+                Err(e) => println!("Warning: {}", e.to_string().trim_end()),
+            }
+        }
+        if !chunk.chunk_type().is_reserved_bit_valid() {
+            violations.push((c, *chunk.chunk_type()));
+        }
+        if args.palette && *chunk.chunk_type() == *"PLTE" && plte.is_none() {
+            plte = Some(chunk);
+        } else if args.palette && *chunk.chunk_type() == *"tRNS" && trns.is_none() {
+            trns = Some(chunk);
+        }
+    }
+    if args.palette {
+        print_palette(ihdr.as_ref(), plte.as_ref(), trns.as_ref());
+    }
+    let trailer = reader.into_trailer_stats()?; // Counted as it streams, however long it is
+    if !trailer.is_empty() {
+        println!("{}", trailer_summary(&trailer));
+    }
+    for (c, chunk_type) in violations {
+        println!("Warning: chunk {c} ({chunk_type}) has its reserved bit set");
+    }
     Ok(())
 }
 
+/// Palette entries as RGBA, or why they cannot be shown
+fn print_palette(ihdr: Option<&Ihdr>, plte: Option<&Chunk>, trns: Option<&Chunk>) {
+    let (Some(ihdr), Some(plte)) = (ihdr, plte) else {
+        println!("No palette");
+        return;
+    };
+    let palette = match Palette::parse(plte.data(), ihdr) {
+        Ok(palette) => palette,
+        Err(e) => return println!("Warning: {}", e.to_string().trim_end()),
+    };
+    let transparency = trns.and_then(|trns| match Transparency::parse(trns.data(), ihdr, Some(&palette)) {
+        Ok(transparency) => Some(transparency),
+        Err(e) => {
+            println!("Warning: {}", e.to_string().trim_end()); // Entries are still shown, as opaque
+            None
+        }
+    });
+    println!("Palette: {} entries", palette.len());
+    for (i, [r, g, b, a]) in palette.rgba(transparency.as_ref()).into_iter().enumerate() {
+        println!("Entry {i}: rgba({r}, {g}, {b}, {a})");
+    }
+}
+
 pub fn remove(args: RemoveArgs) -> R<()> {
-    let contents = contents(&args.file_path)?;
-    let mut png = Png::try_from(contents.as_slice())?;
-    png.remove_chunk(&args.chunk_type)?;
-    let new_contents = png.as_bytes();
-    
-    fs::write(args.file_path, new_contents).map_err(|_| Error::FileError)?; // We overwrite in all cases (so the original png file was mutated)
+    let options = ParseOptions { preserve_raw: args.raw, ..Default::default() };
+    let mut editor = Editor::open(args.file_path, &options)?.backup(args.backup);
+    if let Some(path) = args.output_file {
+        editor = editor.output(path); // If specified, use given path, else overwrite
+    }
+    warn_reserved_bits(editor.png());
+    let c_type = args.chunk_type;
+    let matches: Vec<usize> = editor.png().chunks_by_type(&c_type).map(|(i, _)| i).collect();
+    if args.all && !matches.is_empty() {
+        for index in matches.into_iter().rev() {
+            editor.remove(index)?; // Last first, so that the other indices still hold
+        }
+    } else {
+        let index = matches
+            .get(args.index.unwrap_or(0))
+            .ok_or(Error::ChunkNotFound(c_type.to_string()))?;
+        editor.remove(*index)?;
+    }
+    stamp_if(&mut editor, args.touch)?;
+    editor.commit()?;
+    Ok(())
+}
+
+/// Salvages what can be read from a damaged png into a new file, reporting every damage found
+pub fn recover(args: RecoverArgs) -> R<()> {
+    let (png, diagnostics) = Png::recover(&contents(&args.file_path)?, &ParseOptions::default());
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+    println!("Salvaged {} chunks ({} damages found)", png.chunks().len(), diagnostics.len());
+    if !png.trailer().is_empty() {
+        println!("{}", trailer_summary(&ByteStats::of(png.trailer())));
+    }
+    png.save(&args.output_file, false)?;
+    Ok(())
+}
+
+/// Reports every spec violation of a png, failing if any of them is an error
+pub fn validate(args: ValidateArgs) -> R<()> {
+    let png = Png::try_from(contents(&args.file_path)?.as_slice())?;
+    let issues = png.validate();
+    for issue in &issues {
+        println!("{issue}");
+    }
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    if errors > 0 {
+        return Err(Error::NonConforming(errors));
+    }
+    println!("Png is conforming");
+    Ok(())
+}
+
+/// Extracts, strips or sets the raw bytes following IEND
+pub fn trailer(args: TrailerArgs) -> R<()> {
+    match args {
+        TrailerArgs::Extract { file_path, output_file } => {
+            let png = Png::try_from(contents(&file_path)?.as_slice())?;
+            fs::write(output_file, png.trailer()).map_err(|_| Error::FileError)?;
+            println!("{}", trailer_summary(&ByteStats::of(png.trailer())));
+        }
+        TrailerArgs::Strip { file_path, output_file, touch } => set_trailer(file_path, output_file, Vec::new(), touch)?,
+        TrailerArgs::Set { file_path, data_file, output_file, touch } => {
+            let trailer = contents(&data_file)?;
+            set_trailer(file_path, output_file, trailer, touch)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the trailer, writing to `output_file` if given, else overwriting `file_path`
+fn set_trailer(file_path: PathBuf, output_file: Option<PathBuf>, trailer: Vec<u8>, touch: bool) -> R<()> {
+    let mut editor = editor(file_path, output_file, false)?;
+    stamp_if(&mut editor, touch)?; // The trailer is not an editor operation, so it is set on the edited png
+    let path = editor.path().to_path_buf();
+    let mut png = editor.into_png();
+    png.set_trailer(trailer)?;
+    png.save(&path, false)
+}
+
+/// Chunk level differences between two pngs
+pub fn diff(args: DiffArgs) -> R<()> {
+    let old = Png::try_from(contents(&args.old_file)?.as_slice())?;
+    let new = Png::try_from(contents(&args.new_file)?.as_slice())?;
+    let changes = old.diff(&new);
+    for change in &changes {
+        if args.porcelain {
+            println!("{}", change.porcelain());
+        } else {
+            println!("{change}");
+        }
+    }
+    if changes.is_empty() && !args.porcelain {
+        println!("No differences");
+    }
     Ok(())
 }
 
+/// Sets, gets, lists or removes standard textual metadata
+pub fn text(args: TextArgs) -> R<()> {
+    let options = ParseOptions::default();
+    match args {
+        TextArgs::Set { file_path, keyword, text, output_file, language, translated_keyword, compress, backup, touch } => {
+            let international = language.is_some() || translated_keyword.is_some() || text.chars().any(|c| c as u32 > 0xFF);
+            let text: Text = match international {
+                true => {
+                    let (language, translated) = (language.unwrap_or_default(), translated_keyword.unwrap_or_default());
+                    InternationalText::new(&keyword, &language, &translated, &text)?.compressed(compress).into()
+                }
+                false => TextChunk::new(&keyword, &text)?.compressed(compress).into(),
+            };
+            let mut editor = editor(file_path, output_file, backup)?;
+            editor.set_text(text, &options)?; // Only one value per keyword and language
+            stamp_if(&mut editor, touch)?;
+            editor.commit()?;
+        }
+        TextArgs::Get { file_path, keyword, language } => {
+            let mut found = false;
+            for_each_text(&file_path, &options, |text| {
+                if text.matches(&keyword, language.as_deref()) {
+                    println!("{}", text.text());
+                    found = true;
+                }
+            })?;
+            if !found {
+                return Err(Error::ChunkNotFound(format!("text {keyword}")));
+            }
+        }
+        TextArgs::List { file_path } => for_each_text(&file_path, &options, |text| println!("{text}"))?,
+        TextArgs::Remove { file_path, keyword, output_file, language, backup, touch } => {
+            let mut editor = editor(file_path, output_file, backup)?;
+            let matches: Vec<usize> = editor
+                .png()
+                .texts(&options)
+                .filter(|(_, text)| text.as_ref().is_ok_and(|text| text.matches(&keyword, language.as_deref())))
+                .map(|(i, _)| i)
+                .collect();
+            if matches.is_empty() {
+                return Err(Error::ChunkNotFound(format!("text {keyword}")));
+            }
+            for index in matches.into_iter().rev() {
+                editor.remove(index)?; // Last first, so that the other indices still hold
+            }
+            stamp_if(&mut editor, touch)?;
+            editor.commit()?;
+        }
+    }
+    Ok(())
+}
+
+/// Streams the text chunks (zTXt and compressed iTXt inflated), warning about the ones which cannot be decoded
+fn for_each_text<F: FnMut(&Text)>(path: &PathBuf, options: &ParseOptions, mut f: F) -> R<()> {
+    for (c, chunk) in ChunkReader::with_options(input(path)?, options.clone())?.enumerate() {
+        match Text::from_chunk(&chunk?, options) {
+            Some(Ok(text)) => f(&text),
+            Some(Err(e)) => eprintln!("Warning: chunk {c}: {}", e.to_string().trim_end()),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Stamps the edited png with the current time, if asked to
+fn stamp_if(editor: &mut Editor, touch: bool) -> R<()> {
+    if touch {
+        editor.touch(ImageTime::now())?;
+    }
+    Ok(())
+}
+
+/// Edit session writing to `output_file` if given, else overwriting `file_path`
+fn editor(file_path: PathBuf, output_file: Option<PathBuf>, backup: bool) -> R<Editor> {
+    let editor = Editor::open(file_path, &ParseOptions::default())?.backup(backup);
+    Ok(match output_file {
+        Some(path) => editor.output(path),
+        None => editor,
+    })
+}
+
+/// Non-conforming (but still read) chunk types are reported on stderr
+fn warn_reserved_bits(png: &Png) {
+    for (c, chunk) in png.reserved_bit_violations() {
+        warn_reserved_bit(c, chunk);
+    }
+}
+
+fn warn_reserved_bit(c: usize, chunk: &Chunk) {
+    if !chunk.chunk_type().is_reserved_bit_valid() {
+        eprintln!("Warning: chunk {c} ({}) has its reserved bit set", chunk.chunk_type());
+    }
+}
+
 pub fn contents(path: &PathBuf) -> R<Vec<u8>> {
     fs::read(path).map_err(|_| Error::FileError)
 }
+
+/// Buffered reader over a file, or over stdin when the path is `-`
+pub fn input(path: &PathBuf) -> R<Box<dyn Read>> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(BufReader::new(io::stdin())));
+    }
+    let file = File::open(path).map_err(|_| Error::FileError)?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_decode_auto_skips_binary_chunks() {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec());
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("vnDr", &[0xFF, 0xFE, 0x00, 0x80]), // Not UTF-8
+            chunk("ruSt", b"hidden message"),
+            chunk("IEND", b""),
+        ]);
+        let path = std::env::temp_dir().join(format!("pngsct-decode-{}.png", std::process::id()));
+        fs::write(&path, png.as_bytes()).unwrap();
+        let args = |all, index| DecodeArgs {
+            file_path: path.clone(),
+            chunk_type: "auto".to_string(),
+            type_from_key: false,
+            index,
+            all,
+        };
+        assert!(decode(args(true, None)).is_ok());
+        assert!(decode(args(false, None)).is_ok()); // The message is the first match
+        assert!(matches!(decode(args(false, Some(1))), Err(Error::ChunkNotFound(_))));
+        let explicit = DecodeArgs { chunk_type: "vnDr".to_string(), ..args(false, None) };
+        assert!(decode(explicit).is_err()); // Asked for by name, so not skipped
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{ParseOptions, Png};
+use crate::text::Text;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+/// A difference between two pngs, chunks being referred to by their index in each of them
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added { to: usize, chunk_type: ChunkType },
+    Removed { from: usize, chunk_type: ChunkType },
+    Moved { from: usize, to: usize, chunk_type: ChunkType }, // Same content, out of the original order
+    Modified {
+        from: usize,
+        to: usize,
+        chunk_type: ChunkType,
+        bytes: ByteSummary,
+        text: Option<(String, String)>, // Decoded old and new text, for text chunks
+    },
+    Trailer { old_len: usize, new_len: usize }, // The bytes after IEND differ
+}
+
+/// How the data of a modified chunk changed
+#[derive(Debug, PartialEq, Eq)]
+pub struct ByteSummary {
+    pub old_len: usize,
+    pub new_len: usize,
+    pub differing: usize,        // Bytes differing at the same offset, plus the length difference
+    pub first_difference: usize, // Offset of the first differing byte
+}
+
+impl ByteSummary {
+    fn new(old: &[u8], new: &[u8]) -> Self {
+        let common = old.len().min(new.len());
+        let first_difference = (0..common).find(|&i| old[i] != new[i]).unwrap_or(common);
+        let differing = (0..common).filter(|&i| old[i] != new[i]).count() + old.len().abs_diff(new.len());
+        Self {
+            old_len: old.len(),
+            new_len: new.len(),
+            differing,
+            first_difference,
+        }
+    }
+}
+
+impl Change {
+    /// Tab separated fields, for scripts: kind, old index, new index, chunk type, then details (`-` when missing)
+    pub fn porcelain(&self) -> String {
+        match self {
+            Change::Added { to, chunk_type } => format!("added\t-\t{to}\t{chunk_type}\t-"),
+            Change::Removed { from, chunk_type } => format!("removed\t{from}\t-\t{chunk_type}\t-"),
+            Change::Moved { from, to, chunk_type } => format!("moved\t{from}\t{to}\t{chunk_type}\t-"),
+            Change::Modified { from, to, chunk_type, bytes, .. } => format!(
+                "modified\t{from}\t{to}\t{chunk_type}\t{} {} {} {}",
+                bytes.old_len, bytes.new_len, bytes.differing, bytes.first_difference
+            ),
+            Change::Trailer { old_len, new_len } => format!("trailer\t-\t-\t-\t{old_len} {new_len}"),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { to, chunk_type } => write!(f, "+ chunk {to} ({chunk_type}) added"),
+            Change::Removed { from, chunk_type } => write!(f, "- chunk {from} ({chunk_type}) removed"),
+            Change::Moved { from, to, chunk_type } => write!(f, "~ chunk {from} ({chunk_type}) moved to {to}"),
+            Change::Modified { from, to, chunk_type, bytes, text } => {
+                write!(
+                    f,
+                    "* chunk {from} ({chunk_type}) modified as chunk {to}: {} -> {} bytes, {} differing from byte {}",
+                    bytes.old_len, bytes.new_len, bytes.differing, bytes.first_difference
+                )?;
+                if let Some((old, new)) = text {
+                    for line in text_diff(old, new) {
+                        write!(f, "\n    {line}")?;
+                    }
+                }
+                Ok(())
+            }
+            Change::Trailer { old_len, new_len } => {
+                write!(f, "* trailing data modified: {old_len} -> {new_len} bytes")
+            }
+        }
+    }
+}
+
+/// Lines of `old` and `new` around their common start and end, as `-` and `+` lines
+fn text_diff(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let removed = old[prefix..old.len() - suffix].iter().map(|line| format!("- {line}"));
+    let added = new[prefix..new.len() - suffix].iter().map(|line| format!("+ {line}"));
+    removed.chain(added).collect()
+}
+
+/// Text of a tEXt, zTXt or iTXt chunk, as shown by `text list`
+fn decoded_text(chunk: &Chunk) -> Option<String> {
+    Text::from_chunk(chunk, &ParseOptions::default())?.ok().map(|text| text.to_string())
+}
+
+/// Indices of the longest increasing subsequence of `values`
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    let mut tails: Vec<usize> = Vec::new(); // Index ending the best subsequence of each length
+    let mut previous = vec![None; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&t| values[t] < value);
+        previous[i] = length.checked_sub(1).map(|l| tails[l]);
+        if length == tails.len() {
+            tails.push(i);
+        } else {
+            tails[length] = i;
+        }
+    }
+    let mut sequence = Vec::new();
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        sequence.push(i);
+        current = previous[i];
+    }
+    sequence.reverse();
+    sequence
+}
+
+impl Png {
+    /// Chunk level differences from `self` to `other`: identical chunks (same type and content hash) are paired
+    /// first, those out of their original order being reported as moved, then the remaining chunks of a same type
+    /// are paired in order as modified, and whatever is left was removed or added
+    pub fn diff(&self, other: &Png) -> Vec<Change> {
+        let (old, new) = (self.chunks(), other.chunks());
+
+        // Identical chunks, the n-th occurrence on one side going with the n-th on the other
+        let mut identical: HashMap<([u8; 4], u32), VecDeque<usize>> = HashMap::new();
+        for (i, chunk) in new.iter().enumerate() {
+            identical.entry((chunk.chunk_type().bytes(), chunk.crc())).or_default().push_back(i);
+        }
+        let mut pairs = Vec::new();
+        for (i, chunk) in old.iter().enumerate() {
+            let Some(candidates) = identical.get_mut(&(chunk.chunk_type().bytes(), chunk.crc())) else {
+                continue;
+            };
+            if let Some(c) = candidates.iter().position(|&j| new[j].data() == chunk.data()) {
+                pairs.push((i, candidates.remove(c).unwrap())); // Data compared, in case of a CRC collision
+            }
+        }
+        let mut old_paired = vec![false; old.len()];
+        let mut new_paired = vec![false; new.len()];
+        for &(i, j) in &pairs {
+            old_paired[i] = true;
+            new_paired[j] = true;
+        }
+        let identical_pairs = pairs.len();
+
+        // Other chunks of a same type, in order
+        let mut remaining: HashMap<[u8; 4], VecDeque<usize>> = HashMap::new();
+        for (j, chunk) in new.iter().enumerate().filter(|(j, _)| !new_paired[*j]) {
+            remaining.entry(chunk.chunk_type().bytes()).or_default().push_back(j);
+        }
+        let mut changes = Vec::new();
+        for (from, chunk) in old.iter().enumerate().filter(|(i, _)| !old_paired[*i]) {
+            let chunk_type = *chunk.chunk_type();
+            match remaining.get_mut(&chunk_type.bytes()).and_then(VecDeque::pop_front) {
+                Some(to) => {
+                    new_paired[to] = true;
+                    pairs.push((from, to));
+                    let text = decoded_text(chunk).zip(decoded_text(&new[to]));
+                    let bytes = ByteSummary::new(chunk.data(), new[to].data());
+                    changes.push(Change::Modified { from, to, chunk_type, bytes, text });
+                }
+                None => changes.push(Change::Removed { from, chunk_type }),
+            }
+        }
+
+        // Identical chunks out of the order kept by most paired chunks were moved
+        let mut order: Vec<usize> = (0..pairs.len()).collect();
+        order.sort_by_key(|&p| pairs[p].0);
+        let in_order = longest_increasing(&order.iter().map(|&p| pairs[p].1).collect::<Vec<_>>());
+        let mut in_order = in_order.into_iter().peekable();
+        for (o, &p) in order.iter().enumerate() {
+            let (from, to) = pairs[p];
+            if in_order.next_if_eq(&o).is_none() && p < identical_pairs {
+                changes.push(Change::Moved { from, to, chunk_type: *old[from].chunk_type() });
+            }
+        }
+        for (to, chunk) in new.iter().enumerate().filter(|(j, _)| !new_paired[*j]) {
+            changes.push(Change::Added { to, chunk_type: *chunk.chunk_type() });
+        }
+
+        if self.trailer() != other.trailer() {
+            changes.push(Change::Trailer {
+                old_len: self.trailer().len(),
+                new_len: other.trailer().len(),
+            });
+        }
+        changes.sort_by_key(|change| match change {
+            Change::Removed { from, .. } => (0, *from),
+            Change::Added { to, .. } | Change::Moved { to, .. } | Change::Modified { to, .. } => (1, *to),
+            Change::Trailer { .. } => (2, 0), // Last, as the trailer follows every chunk
+        });
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Comment\0first line\nsecond line"),
+            chunk("IDAT", "image data"),
+            chunk("ruSt", "message"),
+            chunk("IEND", ""),
+        ])
+    }
+
+    #[test]
+    fn test_identical() {
+        assert!(testing_png().diff(&testing_png()).is_empty());
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = testing_png();
+        let mut new = testing_png();
+        new.move_chunk(3, 1).unwrap(); // IHDR ruSt tEXt IDAT IEND
+        new.chunks_mut().nth(2).unwrap().set_data(b"Comment\0first line\nnew line".to_vec());
+        new.remove_chunk_at(3).unwrap();
+        new.insert_chunk_at(3, chunk("IDAT", "other image data")).unwrap();
+        new.insert_chunk_at(3, chunk("tIME", "time")).unwrap(); // IHDR ruSt tEXt tIME IDAT IEND
+        new.set_trailer(b"trailing".to_vec()).unwrap();
+
+        let changes = old.diff(&new);
+        let kinds: Vec<String> = changes.iter().map(|c| c.porcelain()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "moved\t3\t1\truSt\t-",
+                "modified\t1\t2\ttEXt\t30 27 10 19",
+                "added\t-\t3\ttIME\t-",
+                "modified\t2\t4\tIDAT\t10 16 15 0",
+                "trailer\t-\t-\t-\t0 8",
+            ]
+        );
+        assert!(changes[1].to_string().ends_with("- second line\n    + new line"));
+    }
+
+    #[test]
+    fn test_removed() {
+        let old = testing_png();
+        let mut new = testing_png();
+        new.remove_chunk_at(3).unwrap();
+        assert_eq!(
+            old.diff(&new),
+            vec![Change::Removed {
+                from: 3,
+                chunk_type: ChunkType::from_str("ruSt").unwrap()
+            }]
+        );
+    }
+}
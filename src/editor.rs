@@ -0,0 +1,327 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::placement::Place;
+use crate::png::{ParseOptions, Png};
+use crate::utils::{Error, Result};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An edit of the chunk sequence, as recorded in an `Editor` log: placed inserts keep their `Place`, and removals and
+/// replacements name their chunk by type and occurrence, so that replaying on a png with another layout still
+/// does what was meant (or fails)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Append(Chunk),
+    Insert(usize, Chunk), // At this very index
+    Place(Place, Chunk),  // Following the placement policy (see `Png::insert_chunk_placed`)
+    Remove(Target),
+    Replace(Target, Chunk),
+}
+
+/// The `occurrence`-th chunk (from 0) of some type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub chunk_type: ChunkType,
+    pub occurrence: usize,
+}
+
+impl Target {
+    /// Names the chunk at `index` of `png`
+    pub fn at(png: &Png, index: usize) -> Result<Self> {
+        let chunk_type = *png.chunks().get(index).ok_or(Error::IndexOutOfRange(index))?.chunk_type();
+        let occurrence = png.chunks()[..index].iter().filter(|c| *c.chunk_type() == chunk_type).count();
+        Ok(Self { chunk_type, occurrence })
+    }
+
+    /// Index of the chunk in `png` (`ChunkNotFound` if it has fewer chunks of that type)
+    pub fn index_in(&self, png: &Png) -> Result<usize> {
+        png.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| *c.chunk_type() == self.chunk_type)
+            .nth(self.occurrence)
+            .map(|(i, _)| i)
+            .ok_or(Error::ChunkNotFound(self.to_string()))
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} #{}", self.chunk_type, self.occurrence)
+    }
+}
+
+impl Operation {
+    /// Applies the operation with the usual placement checks, giving back the index it applied at and the chunk it took
+    /// out, if any
+    pub fn apply(&self, png: &mut Png) -> Result<(usize, Option<Chunk>)> {
+        match self {
+            Operation::Append(chunk) => png.append_chunk(chunk.clone()).map(|index| (index, None)),
+            Operation::Insert(index, chunk) => png.insert_chunk_at(*index, chunk.clone()).map(|_| (*index, None)),
+            Operation::Place(place, chunk) => png.insert_chunk_placed(chunk.clone(), *place).map(|index| (index, None)),
+            Operation::Remove(target) => {
+                let index = target.index_in(png)?;
+                png.remove_chunk_at(index).map(|taken| (index, Some(taken)))
+            }
+            Operation::Replace(target, chunk) => {
+                let index = target.index_in(png)?;
+                png.replace_chunk_at(index, chunk.clone()).map(|taken| (index, Some(taken)))
+            }
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chunk = |chunk: &Chunk| format!("{} ({} bytes)", chunk.chunk_type(), chunk.length());
+        match self {
+            Operation::Append(c) => write!(f, "append {}", chunk(c)),
+            Operation::Insert(index, c) => write!(f, "insert {} at {index}", chunk(c)),
+            Operation::Place(place, c) => write!(f, "insert {} {place}", chunk(c)),
+            Operation::Remove(target) => write!(f, "remove {target}"),
+            Operation::Replace(target, c) => write!(f, "replace {target} with {}", chunk(c)),
+        }
+    }
+}
+
+/// An applied operation, with where it applied and what it took out of the png so that it can be undone
+#[derive(Debug)]
+struct Done {
+    operation: Operation,
+    index: usize,
+    taken: Option<Chunk>,
+}
+
+/// Edit session over a png file: every operation is logged, can be undone and redone, or replayed on another png,
+/// and nothing is written before `commit`
+pub struct Editor {
+    png: Png,
+    path: PathBuf, // Where `commit` writes
+    backup: bool,
+    done: Vec<Done>,
+    undone: Vec<Operation>, // Most recently undone last
+}
+
+impl Editor {
+    pub fn new(png: Png, path: PathBuf) -> Self {
+        Self {
+            png,
+            path,
+            backup: false,
+            done: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    /// Parses the png at `path`, to be written back there
+    pub fn open(path: PathBuf, options: &ParseOptions) -> Result<Self> {
+        let contents = fs::read(&path).map_err(|_| Error::FileError)?;
+        Ok(Self::new(Png::parse(&contents, options)?, path))
+    }
+
+    /// Writes to another path on `commit`
+    pub fn output(mut self, path: PathBuf) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Keeps the original file as `<path>.bak` on `commit`
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Png as edited so far
+    pub fn png(&self) -> &Png {
+        &self.png
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Applied operations, oldest first
+    pub fn log(&self) -> impl Iterator<Item = &Operation> {
+        self.done.iter().map(|done| &done.operation)
+    }
+
+    /// Applies and logs an operation, which drops whatever was undone, returning the index it applied at
+    pub fn apply(&mut self, operation: Operation) -> Result<usize> {
+        let (index, taken) = operation.apply(&mut self.png)?;
+        self.done.push(Done { operation, index, taken });
+        self.undone.clear();
+        Ok(index)
+    }
+
+    pub fn append(&mut self, chunk: Chunk) -> Result<()> {
+        self.apply(Operation::Append(chunk)).map(|_| ())
+    }
+
+    /// Removes the chunk at `index`, logged as its type and occurrence
+    pub fn remove(&mut self, index: usize) -> Result<()> {
+        let target = Target::at(&self.png, index)?;
+        self.apply(Operation::Remove(target)).map(|_| ())
+    }
+
+    pub fn insert(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        self.apply(Operation::Insert(index, chunk)).map(|_| ())
+    }
+
+    /// Replaces the chunk at `index`, logged as its type and occurrence
+    pub fn replace(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        let target = Target::at(&self.png, index)?;
+        self.apply(Operation::Replace(target, chunk)).map(|_| ())
+    }
+
+    /// Inserts a chunk following the `place` policy (see `Png::insert_chunk_placed`), returning its index
+    pub fn insert_placed(&mut self, chunk: Chunk, place: Place) -> Result<usize> {
+        self.apply(Operation::Place(place, chunk))
+    }
+
+    /// Reverts the last operation, restoring the png exactly as it was (false when there is nothing to undo)
+    pub fn undo(&mut self) -> bool {
+        let Some(Done { operation, index, taken }) = self.done.pop() else {
+            return false;
+        };
+        let chunks = self.png.chunks_unchecked(); // The earlier state was there already, no check needed
+        match (&operation, taken) {
+            (Operation::Append(_) | Operation::Insert(..) | Operation::Place(..), _) => {
+                chunks.remove(index);
+            }
+            (Operation::Remove(_), Some(taken)) => chunks.insert(index, taken),
+            (Operation::Replace(..), Some(taken)) => chunks[index] = taken,
+            _ => unreachable!("removals and replacements always take a chunk out"),
+        }
+        self.undone.push(operation);
+        true
+    }
+
+    /// Applies the last undone operation again (false when there is nothing to redo)
+    pub fn redo(&mut self) -> Result<bool> {
+        let Some(operation) = self.undone.pop() else {
+            return Ok(false);
+        };
+        let (index, taken) = operation.apply(&mut self.png)?;
+        self.done.push(Done { operation, index, taken });
+        Ok(true)
+    }
+
+    /// Applies the logged operations to another png, each resolved against its own layout, stopping at the first one
+    /// which fails there (such as a removal of a chunk it does not have)
+    pub fn replay(&self, png: &mut Png) -> Result<()> {
+        for operation in self.log() {
+            operation.apply(png)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the edited png atomically (see `Png::save`)
+    pub fn commit(&self) -> Result<()> {
+        self.png.save(&self.path, self.backup)
+    }
+
+    pub fn into_png(self) -> Png {
+        self.png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![chunk("IHDR", "header"), chunk("IDAT", "image data"), chunk("IEND", "")])
+    }
+
+    fn edited(editor: &mut Editor) {
+        editor.insert(2, chunk("ruSt", "message")).unwrap();
+        editor.replace(1, chunk("IDAT", "other image data")).unwrap();
+        editor.insert_placed(chunk("ruSt", "after IEND"), Place::AfterIend).unwrap();
+        editor.remove(2).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = Editor::new(testing_png(), PathBuf::from("image.png"));
+        edited(&mut editor);
+        let after = editor.png().as_bytes();
+        assert_eq!(editor.log().count(), 4);
+
+        while editor.undo() {}
+        assert_eq!(editor.png().as_bytes(), testing_png().as_bytes());
+        while editor.redo().unwrap() {}
+        assert_eq!(editor.png().as_bytes(), after);
+
+        editor.undo();
+        editor.append(chunk("ruSt", "other")).unwrap(); // Drops the undone removal
+        assert_eq!(editor.png().chunks()[3].chunk_type().to_string(), "ruSt"); // Before IEND
+        assert!(!editor.redo().unwrap());
+        editor.undo();
+        assert_eq!(editor.png().chunks().len(), 5);
+    }
+
+    #[test]
+    fn test_replay_and_log() {
+        let mut editor = Editor::new(testing_png(), PathBuf::from("image.png"));
+        edited(&mut editor);
+        let mut other = testing_png();
+        editor.replay(&mut other).unwrap();
+        assert_eq!(other.as_bytes(), editor.png().as_bytes());
+
+        let log: Vec<String> = editor.log().map(|operation| operation.to_string()).collect();
+        assert_eq!(log[0], "insert ruSt (7 bytes) at 2");
+        assert_eq!(log[2], "insert ruSt (10 bytes) after-iend");
+        assert_eq!(log[3], "remove ruSt #0");
+    }
+
+    #[test]
+    fn test_replay_on_other_layout() {
+        let mut editor = Editor::new(testing_png(), PathBuf::from("image.png"));
+        editor.insert_placed(chunk("ruSt", "message"), Place::BeforeIend).unwrap();
+        editor.replace(1, chunk("IDAT", "other image data")).unwrap();
+        editor.remove(2).unwrap(); // The message
+
+        // More chunks, elsewhere: every operation is resolved against this layout
+        let mut other = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Title\0other"),
+            chunk("IDAT", "first"),
+            chunk("IDAT", "second"),
+            chunk("IEND", ""),
+        ]);
+        editor.replay(&mut other).unwrap();
+        let expected = [
+            ("IHDR", "header"),
+            ("tEXt", "Title\0other"),
+            ("IDAT", "other image data"),
+            ("IDAT", "second"),
+            ("IEND", ""),
+        ];
+        let chunks: Vec<(String, String)> = other
+            .chunks()
+            .iter()
+            .map(|c| (c.chunk_type().to_string(), c.data_as_string().unwrap()))
+            .collect();
+        assert_eq!(chunks, expected.map(|(t, d)| (t.to_string(), d.to_string())));
+
+        // Fails rather than removing another chunk when the target is missing
+        let mut editor = Editor::new(other, PathBuf::from("image.png"));
+        editor.remove(3).unwrap(); // Second IDAT
+        assert!(matches!(editor.replay(&mut testing_png()), Err(Error::ChunkNotFound(_))));
+    }
+
+    #[test]
+    fn test_failed_operation_not_logged() {
+        let mut editor = Editor::new(testing_png(), PathBuf::from("image.png"));
+        assert!(editor.remove(0).is_err()); // IHDR has to stay
+        assert!(editor.insert(9, chunk("ruSt", "message")).is_err());
+        assert_eq!(editor.log().count(), 0);
+        assert!(!editor.undo());
+    }
+}
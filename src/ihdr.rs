@@ -0,0 +1,222 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{Png, PngRef};
+use crate::utils::{Error, Result};
+use std::fmt::Display;
+
+/// How pixels are stored, from the IHDR colour type byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourType {
+    Greyscale = 0,
+    Truecolour = 2,
+    Indexed = 3,
+    GreyscaleAlpha = 4,
+    TruecolourAlpha = 6,
+}
+
+impl ColourType {
+    pub fn from_byte(byte: u8) -> Option<ColourType> {
+        match byte {
+            0 => Some(ColourType::Greyscale),
+            2 => Some(ColourType::Truecolour),
+            3 => Some(ColourType::Indexed),
+            4 => Some(ColourType::GreyscaleAlpha),
+            6 => Some(ColourType::TruecolourAlpha),
+            _ => None,
+        }
+    }
+
+    /// Bit depths the specification allows with this colour type
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColourType::Greyscale => &[1, 2, 4, 8, 16],
+            ColourType::Indexed => &[1, 2, 4, 8],
+            ColourType::Truecolour | ColourType::GreyscaleAlpha | ColourType::TruecolourAlpha => &[8, 16],
+        }
+    }
+
+    /// Samples per pixel
+    pub fn channels(&self) -> u8 {
+        match self {
+            ColourType::Greyscale | ColourType::Indexed => 1,
+            ColourType::GreyscaleAlpha => 2,
+            ColourType::Truecolour => 3,
+            ColourType::TruecolourAlpha => 4,
+        }
+    }
+}
+
+impl Display for ColourType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColourType::Greyscale => "greyscale",
+            ColourType::Truecolour => "truecolour",
+            ColourType::Indexed => "indexed-colour",
+            ColourType::GreyscaleAlpha => "greyscale with alpha",
+            ColourType::TruecolourAlpha => "truecolour with alpha",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Image header: geometry and pixel format of the image, decoded from the IHDR chunk data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub colour_type: ColourType,
+    pub compression_method: u8, // Only 0 (deflate) is defined
+    pub filter_method: u8,      // Only 0 (adaptive filtering) is defined
+    pub interlaced: bool,       // Adam7 interlacing
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13; // Data bytes of an IHDR chunk
+    pub const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+    /// Bits taken by a pixel (a palette index for indexed-colour images)
+    pub fn bits_per_pixel(&self) -> u32 {
+        self.bit_depth as u32 * self.colour_type.channels() as u32
+    }
+
+    /// IHDR chunk data
+    pub fn as_bytes(&self) -> [u8; Self::LENGTH] {
+        let mut bytes = [0; Self::LENGTH];
+        bytes[..4].copy_from_slice(&self.width.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.height.to_be_bytes());
+        bytes[8] = self.bit_depth;
+        bytes[9] = self.colour_type as u8;
+        bytes[10] = self.compression_method;
+        bytes[11] = self.filter_method;
+        bytes[12] = self.interlaced as u8;
+        bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::try_from(*b"IHDR").unwrap(), self.as_bytes().to_vec())
+    }
+}
+
+/// Decodes and checks IHDR chunk data: dimensions, bit depth and colour type combination, and methods
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let invalid = |why: String| Err(Error::InvalidData(format!("IHDR {why}")));
+        if bytes.len() != Self::LENGTH {
+            return invalid(format!("is {} bytes long instead of {}", bytes.len(), Self::LENGTH));
+        }
+        let width = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        let height = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+        for (name, dimension) in [("width", width), ("height", height)] {
+            if dimension == 0 || dimension > Self::MAX_DIMENSION {
+                return invalid(format!("{name} {dimension} is out of range"));
+            }
+        }
+        let Some(colour_type) = ColourType::from_byte(bytes[9]) else {
+            return invalid(format!("colour type {} is unknown", bytes[9]));
+        };
+        let bit_depth = bytes[8];
+        if !colour_type.allowed_bit_depths().contains(&bit_depth) {
+            return invalid(format!("bit depth {bit_depth} is not allowed for {colour_type} images"));
+        }
+        if bytes[10] != 0 {
+            return invalid(format!("compression method {} is unknown", bytes[10]));
+        }
+        if bytes[11] != 0 {
+            return invalid(format!("filter method {} is unknown", bytes[11]));
+        }
+        if bytes[12] > 1 {
+            return invalid(format!("interlace method {} is unknown", bytes[12]));
+        }
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            colour_type,
+            compression_method: bytes[10],
+            filter_method: bytes[11],
+            interlaced: bytes[12] == 1,
+        })
+    }
+}
+
+impl Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{}, {}-bit {}, {}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.colour_type,
+            if self.interlaced { "interlaced" } else { "not interlaced" }
+        )
+    }
+}
+
+/// The image header of a chunk sequence, which has to be its first chunk
+fn first_ihdr(first: Option<(&ChunkType, &[u8])>) -> Result<Ihdr> {
+    match first {
+        Some((chunk_type, data)) if *chunk_type == *"IHDR" => Ihdr::try_from(data),
+        _ => Err(Error::ChunkNotFound("IHDR".to_string())),
+    }
+}
+
+impl Png {
+    /// Decoded image header (the first chunk has to be a valid IHDR)
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        first_ihdr(self.chunks().first().map(|c| (c.chunk_type(), c.data())))
+    }
+}
+
+impl PngRef<'_> {
+    /// Decoded image header (the first chunk has to be a valid IHDR)
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        first_ihdr(self.chunks().first().map(|c| (c.chunk_type(), c.data())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_bytes(bit_depth: u8, colour_type: u8) -> [u8; 13] {
+        [0, 0, 1, 0, 0, 0, 0, 200, bit_depth, colour_type, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_decode() {
+        let ihdr = Ihdr::try_from(&ihdr_bytes(16, 6)[..]).unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (256, 200));
+        assert_eq!(ihdr.colour_type, ColourType::TruecolourAlpha);
+        assert_eq!(ihdr.bits_per_pixel(), 64);
+        assert_eq!(ihdr.as_bytes(), ihdr_bytes(16, 6));
+        assert_eq!(ihdr.to_string(), "256x200, 16-bit truecolour with alpha, not interlaced");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Ihdr::try_from(&ihdr_bytes(16, 3)[..]).is_err()); // No 16-bit palettes
+        assert!(Ihdr::try_from(&ihdr_bytes(4, 2)[..]).is_err());
+        assert!(Ihdr::try_from(&ihdr_bytes(8, 5)[..]).is_err());
+        assert!(Ihdr::try_from(&ihdr_bytes(8, 2)[..12]).is_err());
+        let mut zero_width = ihdr_bytes(8, 2);
+        zero_width[..4].copy_from_slice(&[0; 4]);
+        assert!(Ihdr::try_from(&zero_width[..]).is_err());
+        let mut interlace = ihdr_bytes(8, 2);
+        interlace[12] = 2;
+        assert!(Ihdr::try_from(&interlace[..]).is_err());
+    }
+
+    #[test]
+    fn test_png_ihdr() {
+        let ihdr = Ihdr::try_from(&ihdr_bytes(8, 2)[..]).unwrap();
+        let iend = Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), Vec::new());
+        let png = Png::from_chunks(vec![ihdr.to_chunk(), iend]);
+        assert_eq!(png.ihdr().unwrap(), ihdr);
+        assert_eq!(PngRef::try_from(&png.as_bytes()[..]).unwrap().ihdr().unwrap(), ihdr);
+        assert!(Png::from_chunks(Vec::new()).ihdr().is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::chunk::{crc_of, Chunk};
+use crate::chunk_type::ChunkType;
+use crate::placement::{placement_in, Place};
+use crate::png::{ParseOptions, Png};
+use crate::reader::read_full;
+use crate::utils::{Error, Limit, Result};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Chunk headers of a png file, found by seeking over the chunk data
+struct Layout {
+    types: Vec<ChunkType>,
+    offsets: Vec<u64>, // Where each chunk starts
+    end: u64,          // End of the last chunk, where trailing data starts
+}
+
+/// Walks the chunk headers without reading chunk data, except after IEND, where chunks are only told apart
+/// from trailing data by their CRC (like `Png::parse`). CRCs before IEND are not checked, as that means reading everything
+fn scan<F: Read + Seek>(file: &mut F) -> Result<Layout> {
+    let file_len = file.seek(SeekFrom::End(0)).map_err(|_| Error::FileError)?;
+    file.seek(SeekFrom::Start(0)).map_err(|_| Error::FileError)?;
+    let mut layout = Layout {
+        types: Vec::new(),
+        offsets: Vec::new(),
+        end: Png::STANDARD_HEADER.len() as u64,
+    };
+    let parse_error = |layout: &Layout, source| Error::Parse {
+        offset: layout.end as usize,
+        chunk: layout.types.len(),
+        source: Box::new(source),
+    };
+    let mut header = [0; 8];
+    if read_full(file, &mut header)? < header.len() || header != Png::STANDARD_HEADER {
+        return Err(parse_error(&layout, Error::InvalidPngHeader));
+    }
+
+    let options = ParseOptions::default();
+    let mut iend_seen = false;
+    loop {
+        let mut head = [0; Chunk::DATA_LEN_BYTES + Chunk::CHUNK_TYPE_BYTES];
+        let read = read_full(file, &mut head)?;
+        if read == 0 {
+            break;
+        }
+        let chunk = (|| {
+            if read < head.len() {
+                return Err(Error::Truncated {
+                    needed: Chunk::TOTAL_BYTES,
+                    available: read,
+                });
+            }
+            let length = u32::from_be_bytes(head[..4].try_into().unwrap());
+            options.check_length(length)?;
+            let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&head[4..]).unwrap())?;
+            let size = length as u64 + Chunk::TOTAL_BYTES as u64;
+            if layout.end + size > file_len {
+                return Err(Error::Truncated {
+                    needed: size as usize,
+                    available: (file_len - layout.end) as usize,
+                });
+            }
+            if iend_seen {
+                let mut rest = Vec::new();
+                file.take(length as u64 + Chunk::CRC_BYTES as u64)
+                    .read_to_end(&mut rest)
+                    .map_err(|_| Error::FileError)?;
+                let crc = u32::from_be_bytes(rest[length as usize..].try_into().unwrap());
+                if crc_of(&chunk_type, &rest[..length as usize]) != crc {
+                    return Err(Error::CRC);
+                }
+            }
+            Ok((chunk_type, size))
+        })();
+        match chunk {
+            Ok((chunk_type, size)) => {
+                layout.types.push(chunk_type);
+                layout.offsets.push(layout.end);
+                layout.end += size;
+                iend_seen |= chunk_type == *"IEND";
+                file.seek(SeekFrom::Start(layout.end)).map_err(|_| Error::FileError)?;
+            }
+            Err(_) if iend_seen => break, // Not a chunk after IEND: trailing data starts here
+            Err(e) => return Err(parse_error(&layout, e)),
+        }
+    }
+    Ok(layout)
+}
+
+const BLOCK_SIZE: usize = 64 << 10; // Bytes moved at a time when making room for the chunk
+
+/// Inserts a chunk into a png file following the `place` policy, with the same result as parsing, inserting with
+/// `Png::insert_chunk_placed` and saving, but only rewriting the bytes from the insertion point on: with the default
+/// `BeforeIend`, the cost depends on the size of the chunk (and of anything after IEND), not of the image
+/// The file is modified in place, so an interrupted write leaves it damaged: this trades safety for speed on huge files
+pub fn insert_chunk_in_place<F: Read + Write + Seek>(file: &mut F, chunk: &Chunk, place: Place) -> Result<usize> {
+    if chunk.data().len() > Chunk::MAX_LENGTH as usize {
+        return Err(Error::LimitExceeded {
+            limit: Limit::ChunkSize,
+            max: Chunk::MAX_LENGTH as usize,
+        });
+    }
+    let layout = scan(file)?;
+    let index = placement_in(&layout.types, chunk.chunk_type(), place)?;
+    let offset = layout.offsets.get(index).copied().unwrap_or(layout.end);
+
+    let bytes = chunk.as_bytes();
+    shift_tail(file, offset, bytes.len() as u64)?; // IEND onwards by default
+    file.seek(SeekFrom::Start(offset)).map_err(|_| Error::FileError)?;
+    file.write_all(&bytes).map_err(|_| Error::FileError)?; // The file only grows, so nothing is left to truncate
+    file.flush().map_err(|_| Error::FileError)?;
+    Ok(index)
+}
+
+/// Moves the bytes from `offset` to the end of the file `by` bytes further, one block at a time from the end (so that
+/// no byte is overwritten before being moved, and memory use does not depend on the size of what follows the chunk)
+fn shift_tail<F: Read + Write + Seek>(file: &mut F, offset: u64, by: u64) -> Result<()> {
+    let mut end = file.seek(SeekFrom::End(0)).map_err(|_| Error::FileError)?;
+    let mut block = vec![0; BLOCK_SIZE];
+    while end > offset {
+        let start = end.saturating_sub(BLOCK_SIZE as u64).max(offset);
+        let block = &mut block[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start)).map_err(|_| Error::FileError)?;
+        file.read_exact(block).map_err(|_| Error::FileError)?;
+        file.seek(SeekFrom::Start(start + by)).map_err(|_| Error::FileError)?;
+        file.write_all(block).map_err(|_| Error::FileError)?;
+        end = start;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "image data"),
+            chunk("IDAT", "more image data"),
+            chunk("IEND", ""),
+        ])
+    }
+
+    #[test]
+    fn test_same_as_full_rewrite() {
+        for place in [Place::BeforeIend, Place::AfterIhdr, Place::BeforeIdat, Place::AfterIend] {
+            for chunk_type in ["ruSt", "RuSt", "tEXt"] {
+                let mut png = testing_png();
+                png.set_trailer(b"trailing data".to_vec()).unwrap();
+                let mut file = Cursor::new(png.as_bytes());
+                let index = insert_chunk_in_place(&mut file, &chunk(chunk_type, "message"), place).unwrap();
+                assert_eq!(png.insert_chunk_placed(chunk(chunk_type, "message"), place).unwrap(), index);
+                assert_eq!(file.into_inner(), png.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_tail_longer_than_a_block() {
+        let mut png = testing_png();
+        png.set_trailer((0..3 * BLOCK_SIZE + 5).map(|i| (i % 251) as u8).collect()).unwrap();
+        let mut file = Cursor::new(png.as_bytes());
+        let index = insert_chunk_in_place(&mut file, &chunk("ruSt", "message"), Place::AfterIhdr).unwrap();
+        png.insert_chunk_at(index, chunk("ruSt", "message")).unwrap();
+        assert_eq!(file.into_inner(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        let bytes = testing_png().as_bytes();
+        let mut file = Cursor::new(bytes[..bytes.len() - 20].to_vec());
+        assert!(matches!(
+            insert_chunk_in_place(&mut file, &chunk("ruSt", "message"), Place::BeforeIend),
+            Err(Error::Parse { chunk: 2, .. })
+        ));
+    }
+}
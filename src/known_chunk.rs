@@ -0,0 +1,276 @@
+use crate::chunk_type::ChunkType;
+use std::fmt::Display;
+
+/// Every chunk type defined by the PNG specification (third edition) and the APNG extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownChunk {
+    // Critical
+    Ihdr,
+    Plte,
+    Idat,
+    Iend,
+    // Colour space
+    Chrm,
+    Gama,
+    Iccp,
+    Sbit,
+    Srgb,
+    Cicp,
+    Mdcv,
+    Clli,
+    // Transparency and background
+    Trns,
+    Bkgd,
+    Hist,
+    // Physical and palette information
+    Phys,
+    Splt,
+    Exif,
+    // Time and text
+    Time,
+    Text,
+    Ztxt,
+    Itxt,
+    // APNG
+    Actl,
+    Fctl,
+    Fdat,
+}
+
+/// Where a chunk may sit, relative to the critical chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ordering {
+    First,             // IHDR
+    Last,              // IEND
+    BeforePlteAndIdat, // Colour space chunks
+    AfterPlteBeforeIdat,
+    BeforeIdat,
+    AfterIdat,
+    Anywhere, // Between IHDR and IEND
+}
+
+impl KnownChunk {
+    /// Every known chunk, in specification order
+    pub const ALL: [KnownChunk; 25] = [
+        KnownChunk::Ihdr,
+        KnownChunk::Plte,
+        KnownChunk::Idat,
+        KnownChunk::Iend,
+        KnownChunk::Chrm,
+        KnownChunk::Gama,
+        KnownChunk::Iccp,
+        KnownChunk::Sbit,
+        KnownChunk::Srgb,
+        KnownChunk::Cicp,
+        KnownChunk::Mdcv,
+        KnownChunk::Clli,
+        KnownChunk::Trns,
+        KnownChunk::Bkgd,
+        KnownChunk::Hist,
+        KnownChunk::Phys,
+        KnownChunk::Splt,
+        KnownChunk::Exif,
+        KnownChunk::Time,
+        KnownChunk::Text,
+        KnownChunk::Ztxt,
+        KnownChunk::Itxt,
+        KnownChunk::Actl,
+        KnownChunk::Fctl,
+        KnownChunk::Fdat,
+    ];
+
+    /// Looks a chunk type up in the registry
+    pub fn from_chunk_type(chunk_type: &ChunkType) -> Option<KnownChunk> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.bytes() == chunk_type.bytes())
+    }
+
+    /// 4-letter code
+    pub fn bytes(&self) -> [u8; 4] {
+        *match self {
+            KnownChunk::Ihdr => b"IHDR",
+            KnownChunk::Plte => b"PLTE",
+            KnownChunk::Idat => b"IDAT",
+            KnownChunk::Iend => b"IEND",
+            KnownChunk::Chrm => b"cHRM",
+            KnownChunk::Gama => b"gAMA",
+            KnownChunk::Iccp => b"iCCP",
+            KnownChunk::Sbit => b"sBIT",
+            KnownChunk::Srgb => b"sRGB",
+            KnownChunk::Cicp => b"cICP",
+            KnownChunk::Mdcv => b"mDCV",
+            KnownChunk::Clli => b"cLLI",
+            KnownChunk::Trns => b"tRNS",
+            KnownChunk::Bkgd => b"bKGD",
+            KnownChunk::Hist => b"hIST",
+            KnownChunk::Phys => b"pHYs",
+            KnownChunk::Splt => b"sPLT",
+            KnownChunk::Exif => b"eXIf",
+            KnownChunk::Time => b"tIME",
+            KnownChunk::Text => b"tEXt",
+            KnownChunk::Ztxt => b"zTXt",
+            KnownChunk::Itxt => b"iTXt",
+            KnownChunk::Actl => b"acTL",
+            KnownChunk::Fctl => b"fcTL",
+            KnownChunk::Fdat => b"fdAT",
+        }
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        ChunkType::try_from(self.bytes()).unwrap() // All known codes are ASCII letters
+    }
+
+    /// Human readable name
+    pub fn description(&self) -> &'static str {
+        match self {
+            KnownChunk::Ihdr => "image header",
+            KnownChunk::Plte => "palette",
+            KnownChunk::Idat => "image data",
+            KnownChunk::Iend => "image trailer",
+            KnownChunk::Chrm => "primary chromaticities",
+            KnownChunk::Gama => "image gamma",
+            KnownChunk::Iccp => "embedded ICC profile",
+            KnownChunk::Sbit => "significant bits",
+            KnownChunk::Srgb => "standard RGB colour space",
+            KnownChunk::Cicp => "coding-independent code points",
+            KnownChunk::Mdcv => "mastering display colour volume",
+            KnownChunk::Clli => "content light level information",
+            KnownChunk::Trns => "transparency",
+            KnownChunk::Bkgd => "background colour",
+            KnownChunk::Hist => "image histogram",
+            KnownChunk::Phys => "physical pixel dimensions",
+            KnownChunk::Splt => "suggested palette",
+            KnownChunk::Exif => "exchangeable image file profile",
+            KnownChunk::Time => "last-modification time",
+            KnownChunk::Text => "textual data",
+            KnownChunk::Ztxt => "compressed textual data",
+            KnownChunk::Itxt => "international textual data",
+            KnownChunk::Actl => "animation control",
+            KnownChunk::Fctl => "frame control",
+            KnownChunk::Fdat => "frame data",
+        }
+    }
+
+    /// May the chunk appear more than once?
+    pub fn multiple_allowed(&self) -> bool {
+        matches!(
+            self,
+            KnownChunk::Idat
+                | KnownChunk::Splt
+                | KnownChunk::Text
+                | KnownChunk::Ztxt
+                | KnownChunk::Itxt
+                | KnownChunk::Fctl
+                | KnownChunk::Fdat
+        )
+    }
+
+    pub fn ordering(&self) -> Ordering {
+        match self {
+            KnownChunk::Ihdr => Ordering::First,
+            KnownChunk::Iend => Ordering::Last,
+            KnownChunk::Chrm
+            | KnownChunk::Gama
+            | KnownChunk::Iccp
+            | KnownChunk::Sbit
+            | KnownChunk::Srgb
+            | KnownChunk::Cicp
+            | KnownChunk::Mdcv
+            | KnownChunk::Clli => Ordering::BeforePlteAndIdat,
+            KnownChunk::Trns | KnownChunk::Bkgd | KnownChunk::Hist => Ordering::AfterPlteBeforeIdat,
+            KnownChunk::Plte | KnownChunk::Phys | KnownChunk::Splt | KnownChunk::Exif | KnownChunk::Actl => {
+                Ordering::BeforeIdat
+            }
+            KnownChunk::Fdat => Ordering::AfterIdat,
+            KnownChunk::Idat // Consecutive, checked on its own
+            | KnownChunk::Time
+            | KnownChunk::Text
+            | KnownChunk::Ztxt
+            | KnownChunk::Itxt
+            | KnownChunk::Fctl => Ordering::Anywhere,
+        }
+    }
+
+    /// Must the chunk precede PLTE?
+    pub fn must_precede_plte(&self) -> bool {
+        self.ordering() == Ordering::BeforePlteAndIdat
+    }
+
+    /// Must the chunk follow PLTE (when there is one)?
+    pub fn must_follow_plte(&self) -> bool {
+        self.ordering() == Ordering::AfterPlteBeforeIdat
+    }
+
+    /// Must the chunk precede the first IDAT?
+    pub fn must_precede_idat(&self) -> bool {
+        matches!(
+            self.ordering(),
+            Ordering::First | Ordering::BeforePlteAndIdat | Ordering::AfterPlteBeforeIdat | Ordering::BeforeIdat
+        )
+    }
+
+    /// Must the chunk follow the last IDAT?
+    pub fn must_follow_idat(&self) -> bool {
+        matches!(self.ordering(), Ordering::AfterIdat | Ordering::Last)
+    }
+
+    /// IHDR colour types the chunk may appear with
+    pub fn allowed_colour_types(&self) -> &'static [u8] {
+        match self {
+            KnownChunk::Plte | KnownChunk::Hist => &[2, 3, 6], // hIST needs a PLTE
+            KnownChunk::Trns => &[0, 2, 3],                    // Images with an alpha channel have their own transparency
+            _ => &[0, 2, 3, 4, 6],
+        }
+    }
+
+    /// IHDR colour types the chunk must appear with
+    pub fn required_colour_types(&self) -> &'static [u8] {
+        match self {
+            KnownChunk::Plte => &[3],
+            _ => &[],
+        }
+    }
+}
+
+impl Display for KnownChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.bytes()))
+    }
+}
+
+impl ChunkType {
+    /// Standard chunk this type stands for, if any
+    pub fn known(&self) -> Option<KnownChunk> {
+        KnownChunk::from_chunk_type(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_lookup() {
+        for known in KnownChunk::ALL {
+            assert_eq!(known.chunk_type().known(), Some(known));
+            assert_eq!(known.chunk_type().is_critical(), known.ordering() == Ordering::First
+                || known.ordering() == Ordering::Last
+                || matches!(known, KnownChunk::Plte | KnownChunk::Idat));
+        }
+        assert_eq!(ChunkType::from_str("RuSt").unwrap().known(), None);
+    }
+
+    #[test]
+    fn test_constraints() {
+        assert!(KnownChunk::Gama.must_precede_plte());
+        assert!(KnownChunk::Gama.must_precede_idat());
+        assert!(KnownChunk::Trns.must_follow_plte());
+        assert!(!KnownChunk::Text.must_precede_idat());
+        assert!(KnownChunk::Text.multiple_allowed());
+        assert!(!KnownChunk::Ihdr.multiple_allowed());
+        assert_eq!(KnownChunk::Plte.required_colour_types(), &[3]);
+        assert!(!KnownChunk::Trns.allowed_colour_types().contains(&6));
+    }
+}
@@ -0,0 +1,22 @@
+pub mod args;
+pub mod chunk;
+pub mod chunk_type;
+pub mod commands;
+pub mod diff;
+pub mod editor;
+pub mod ihdr;
+pub mod in_place;
+pub mod known_chunk;
+pub mod palette;
+pub mod placement;
+pub mod png;
+pub mod reader;
+pub mod recovery;
+pub mod text;
+pub mod time;
+pub mod utils;
+pub mod validate;
+pub mod writer;
+pub mod zlib;
+
+pub use utils::{Error, Result};
@@ -1,13 +1,6 @@
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
-mod utils;
-
-use commands::{decode, encode, print, remove};
+use pngsct::args;
+use pngsct::commands::{decode, diff, encode, print, recover, remove, text, trailer, validate};
 use structopt::StructOpt;
-use utils::Error;
 
 fn main() -> Result<(), ()> {
     let res = match args::Args::from_args() {
@@ -15,9 +8,15 @@ fn main() -> Result<(), ()> {
         args::Args::Decode(args) => decode(args),
         args::Args::Remove(args) => remove(args),
         args::Args::Print(args) => print(args),
+        args::Args::Recover(args) => recover(args),
+        args::Args::Validate(args) => validate(args),
+        args::Args::Trailer(args) => trailer(args),
+        args::Args::Diff(args) => diff(args),
+        args::Args::Text(args) => text(args),
     };
     if let Err(my_error) = res {
         println!("{my_error}");
+        std::process::exit(1);
     }
     Ok(())
 }
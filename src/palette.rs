@@ -0,0 +1,172 @@
+use crate::ihdr::{ColourType, Ihdr};
+use crate::png::Png;
+use crate::utils::{Error, Result};
+
+/// Colours of a PLTE chunk, indexed by the pixels of indexed-colour images (a suggested palette for truecolour ones)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<[u8; 3]>, // RGB
+}
+
+impl Palette {
+    pub const MAX_ENTRIES: usize = 256;
+
+    /// Decodes PLTE chunk data: whole RGB entries, no more than the image bit depth can index
+    pub fn parse(data: &[u8], ihdr: &Ihdr) -> Result<Self> {
+        let invalid = |why: String| Err(Error::InvalidData(format!("PLTE {why}")));
+        if data.is_empty() || !data.len().is_multiple_of(3) {
+            return invalid(format!("length {} is not a positive multiple of 3", data.len()));
+        }
+        let entries: Vec<[u8; 3]> = data.chunks_exact(3).map(|rgb| rgb.try_into().unwrap()).collect();
+        let max = match ihdr.colour_type {
+            ColourType::Indexed => Self::MAX_ENTRIES.min(1 << ihdr.bit_depth),
+            _ => Self::MAX_ENTRIES,
+        };
+        if entries.len() > max {
+            return invalid(format!("has {} entries, more than the {max} allowed", entries.len()));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries with their alpha from a tRNS chunk (opaque when it has none for them)
+    pub fn rgba(&self, transparency: Option<&Transparency>) -> Vec<[u8; 4]> {
+        let alphas = match transparency {
+            Some(Transparency::Alphas(alphas)) => alphas.as_slice(),
+            _ => &[],
+        };
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, &[r, g, b])| [r, g, b, alphas.get(i).copied().unwrap_or(255)])
+            .collect()
+    }
+
+    /// PLTE chunk data
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.entries.concat()
+    }
+}
+
+/// Simple transparency of a tRNS chunk, whose layout depends on the colour type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    Grey(u16),          // Greyscale sample value meant as transparent
+    Rgb(u16, u16, u16), // Truecolour value meant as transparent
+    Alphas(Vec<u8>),    // Alpha of the first palette entries (the others are opaque)
+}
+
+impl Transparency {
+    /// Decodes tRNS chunk data, whose length is set by the colour type (and the palette size for indexed images)
+    pub fn parse(data: &[u8], ihdr: &Ihdr, palette: Option<&Palette>) -> Result<Self> {
+        let invalid = |why: String| Err(Error::InvalidData(format!("tRNS {why}")));
+        let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        match ihdr.colour_type {
+            ColourType::Greyscale if data.len() == 2 => Ok(Transparency::Grey(sample(0))),
+            ColourType::Truecolour if data.len() == 6 => Ok(Transparency::Rgb(sample(0), sample(2), sample(4))),
+            ColourType::Greyscale | ColourType::Truecolour => {
+                invalid(format!("length {} does not match {} images", data.len(), ihdr.colour_type))
+            }
+            ColourType::Indexed => {
+                let entries = palette.map_or(Palette::MAX_ENTRIES, Palette::len);
+                if data.len() > entries {
+                    return invalid(format!("has {} alphas for {entries} palette entries", data.len()));
+                }
+                Ok(Transparency::Alphas(data.to_vec()))
+            }
+            ColourType::GreyscaleAlpha | ColourType::TruecolourAlpha => {
+                invalid(format!("is not allowed for {} images", ihdr.colour_type))
+            }
+        }
+    }
+}
+
+impl Png {
+    /// Decoded PLTE chunk (`ChunkNotFound` if there is none)
+    pub fn palette(&self) -> Result<Palette> {
+        let plte = self.chunk_by_type("PLTE").ok_or(Error::ChunkNotFound("PLTE".to_string()))?;
+        Palette::parse(plte.data(), &self.ihdr()?)
+    }
+
+    /// Decoded tRNS chunk (`ChunkNotFound` if there is none)
+    pub fn transparency(&self) -> Result<Transparency> {
+        let trns = self.chunk_by_type("tRNS").ok_or(Error::ChunkNotFound("tRNS".to_string()))?;
+        let palette = match self.palette() {
+            Ok(palette) => Some(palette),
+            Err(Error::ChunkNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Transparency::parse(trns.data(), &self.ihdr()?, palette.as_ref())
+    }
+
+    /// Palette entries as RGBA, with the tRNS alphas if any
+    pub fn palette_rgba(&self) -> Result<Vec<[u8; 4]>> {
+        let transparency = match self.transparency() {
+            Ok(transparency) => Some(transparency),
+            Err(Error::ChunkNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(self.palette()?.rgba(transparency.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn ihdr(bit_depth: u8, colour_type: u8) -> Ihdr {
+        Ihdr::try_from(&[0, 0, 0, 1, 0, 0, 0, 1, bit_depth, colour_type, 0, 0, 0][..]).unwrap()
+    }
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::parse(&[255, 0, 0, 0, 255, 0], &ihdr(1, 3)).unwrap();
+        assert_eq!(palette.entries(), &[[255, 0, 0], [0, 255, 0]]);
+        assert_eq!(palette.as_bytes(), vec![255, 0, 0, 0, 255, 0]);
+        assert!(Palette::parse(&[0; 4], &ihdr(8, 3)).is_err()); // Not whole entries
+        assert!(Palette::parse(&[0; 9], &ihdr(1, 3)).is_err()); // 3 entries for 1 bit indices
+        assert!(Palette::parse(&[0; 9], &ihdr(8, 2)).is_ok()); // Suggested palette
+    }
+
+    #[test]
+    fn test_transparency() {
+        let palette = Palette::parse(&[0; 9], &ihdr(8, 3)).unwrap();
+        assert_eq!(Transparency::parse(&[0, 7], &ihdr(8, 0), None).unwrap(), Transparency::Grey(7));
+        assert_eq!(Transparency::parse(&[0, 1, 0, 2, 0, 3], &ihdr(8, 2), None).unwrap(), Transparency::Rgb(1, 2, 3));
+        assert!(Transparency::parse(&[0; 4], &ihdr(8, 0), None).is_err());
+        assert!(Transparency::parse(&[0; 4], &ihdr(8, 3), Some(&palette)).is_err()); // More alphas than entries
+        assert!(Transparency::parse(&[0; 2], &ihdr(8, 4), None).is_err());
+    }
+
+    #[test]
+    fn test_png_palette_rgba() {
+        let png = Png::from_chunks(vec![
+            ihdr(8, 3).to_chunk(),
+            chunk("PLTE", &[1, 2, 3, 4, 5, 6]),
+            chunk("tRNS", &[0]),
+            chunk("IDAT", b""),
+            chunk("IEND", b""),
+        ]);
+        assert_eq!(png.palette_rgba().unwrap(), vec![[1, 2, 3, 0], [4, 5, 6, 255]]);
+        let no_palette = Png::from_chunks(vec![ihdr(8, 0).to_chunk(), chunk("IEND", b"")]);
+        assert!(matches!(no_palette.palette_rgba(), Err(Error::ChunkNotFound(_))));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::utils::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Where a new chunk goes in a png
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Place {
+    #[default]
+    BeforeIend, // Last chunk of the stream, where ancillary chunks survive decoders and optimisers
+    AfterIhdr,  // First chunk after the header
+    BeforeIdat, // Right before the image data
+    AfterIend,  // Outside of the png stream, where many tools drop it
+}
+
+impl FromStr for Place {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "before-iend" => Ok(Place::BeforeIend),
+            "after-ihdr" => Ok(Place::AfterIhdr),
+            "before-idat" => Ok(Place::BeforeIdat),
+            "after-iend" => Ok(Place::AfterIend),
+            _ => Err(Error::InvalidPlacement(format!(
+                "unknown place {s} (expected before-iend, after-ihdr, before-idat or after-iend)"
+            ))),
+        }
+    }
+}
+
+impl Display for Place {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Place::BeforeIend => "before-iend",
+            Place::AfterIhdr => "after-ihdr",
+            Place::BeforeIdat => "before-idat",
+            Place::AfterIend => "after-iend",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Png {
+    /// Index a chunk of this kind would be inserted at for `place`, moved as little as needed to respect ordering rules:
+    /// - standard chunks follow their `KnownChunk` constraints (PLTE and IDAT relative ordering)
+    /// - other critical chunks have to be read before the image data, so they never go past the first IDAT
+    /// - other unsafe-to-copy chunks may depend on the critical chunks before IDAT, so they never go before PLTE
+    ///
+    /// `AfterIend` is always honoured, as it deliberately puts the chunk out of the png stream
+    pub fn placement_index(&self, chunk: &Chunk, place: Place) -> Result<usize> {
+        let types: Vec<ChunkType> = self.chunks().iter().map(|c| *c.chunk_type()).collect();
+        placement_in(&types, chunk.chunk_type(), place)
+    }
+
+    /// Inserts a chunk following the `place` policy, returning the index it ended up at
+    pub fn insert_chunk_placed(&mut self, chunk: Chunk, place: Place) -> Result<usize> {
+        let index = self.placement_index(&chunk, place)?;
+        if place == Place::AfterIend {
+            self.chunks_unchecked().push(chunk); // Deliberately out of the stream, no placement rule applies
+        } else {
+            self.insert_chunk_at(index, chunk)?;
+        }
+        Ok(index)
+    }
+}
+
+/// `Png::placement_index` on the chunk types alone, for callers which only read chunk headers
+pub(crate) fn placement_in(types: &[ChunkType], chunk_type: &ChunkType, place: Place) -> Result<usize> {
+    let positions = |t: &'static str| types.iter().enumerate().filter(move |(_, c)| **c == *t).map(|(i, _)| i);
+    let position = |t: &'static str| positions(t).next().ok_or(Error::ChunkNotFound(t.to_string()));
+    let index = match place {
+        Place::AfterIhdr => position("IHDR")? + 1,
+        Place::BeforeIdat => position("IDAT")?,
+        Place::BeforeIend => position("IEND")?,
+        Place::AfterIend => return Ok(types.len()),
+    };
+
+    let plte = position("PLTE").ok();
+    let first_idat = position("IDAT").ok();
+    let last_idat = positions("IDAT").next_back();
+    let (before_plte, after_plte, before_idat, after_idat) = match chunk_type.known() {
+        Some(known) => (
+            known.must_precede_plte(),
+            known.must_follow_plte(),
+            known.must_precede_idat(),
+            known.must_follow_idat(),
+        ),
+        None => (false, !chunk_type.is_safe_to_copy(), chunk_type.is_critical(), false),
+    };
+
+    let mut lowest = position("IHDR").map_or(0, |i| i + 1);
+    let mut highest = position("IEND").unwrap_or(types.len());
+    if let (true, Some(p)) = (after_plte, plte) {
+        lowest = lowest.max(p + 1);
+    }
+    if let (true, Some(last)) = (after_idat, last_idat) {
+        lowest = lowest.max(last + 1);
+    }
+    if let (true, Some(p)) = (before_plte, plte) {
+        highest = highest.min(p);
+    }
+    if let (true, Some(first)) = (before_idat, first_idat) {
+        highest = highest.min(first);
+    }
+    Ok(index.max(lowest).min(highest)) // The upper bound wins on conflicting rules, keeping the chunk before the image data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Png {
+        let chunk = |t: &str| Chunk::new(ChunkType::from_str(t).unwrap(), Vec::new());
+        Png::from_chunks(vec![chunk("IHDR"), chunk("gAMA"), chunk("IDAT"), chunk("IDAT"), chunk("tEXt"), chunk("IEND")])
+    }
+
+    fn message(chunk_type: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), b"message".to_vec())
+    }
+
+    #[test]
+    fn test_places() {
+        let expected = [
+            (Place::BeforeIend, 5),
+            (Place::AfterIhdr, 1),
+            (Place::BeforeIdat, 2),
+            (Place::AfterIend, 6),
+        ];
+        for (place, index) in expected {
+            let mut png = png();
+            assert_eq!(png.insert_chunk_placed(message("ruSt"), place).unwrap(), index);
+            assert_eq!(&png.chunks()[index].chunk_type().to_string(), "ruSt");
+        }
+    }
+
+    #[test]
+    fn test_critical_chunk_before_idat() {
+        let mut png = png();
+        assert_eq!(png.insert_chunk_placed(message("RuSt"), Place::BeforeIend).unwrap(), 2);
+        assert_eq!(png.insert_chunk_placed(message("RuSt"), Place::AfterIhdr).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unsafe_to_copy_after_plte() {
+        let mut png = png();
+        png.insert_chunk_at(2, message("PLTE")).unwrap(); // IHDR gAMA PLTE IDAT IDAT tEXt IEND
+        assert_eq!(png.placement_index(&message("ruST"), Place::AfterIhdr).unwrap(), 3); // Unsafe-to-copy, after PLTE
+        assert_eq!(png.placement_index(&message("ruSt"), Place::AfterIhdr).unwrap(), 1); // Safe-to-copy, anywhere
+        assert_eq!(png.placement_index(&message("RuST"), Place::AfterIhdr).unwrap(), 3); // Critical, between PLTE and IDAT
+    }
+
+    #[test]
+    fn test_standard_chunk_constraints() {
+        let mut png = png();
+        png.insert_chunk_at(2, message("PLTE")).unwrap(); // IHDR gAMA PLTE IDAT IDAT tEXt IEND
+        assert_eq!(png.placement_index(&message("sRGB"), Place::BeforeIend).unwrap(), 2); // Before PLTE
+        assert_eq!(png.placement_index(&message("bKGD"), Place::AfterIhdr).unwrap(), 3); // After PLTE, before IDAT
+        assert_eq!(png.placement_index(&message("tEXt"), Place::AfterIhdr).unwrap(), 1); // Anywhere
+    }
+
+    #[test]
+    fn test_place_from_str() {
+        for place in [Place::BeforeIend, Place::AfterIhdr, Place::BeforeIdat, Place::AfterIend] {
+            assert_eq!(Place::from_str(&place.to_string()).unwrap(), place);
+        }
+        assert!(Place::from_str("somewhere").is_err());
+    }
+
+    #[test]
+    fn test_missing_anchor() {
+        let mut png = Png::from_chunks(Vec::new());
+        assert!(matches!(
+            png.insert_chunk_placed(message("ruSt"), Place::BeforeIend),
+            Err(Error::ChunkNotFound(_))
+        ));
+    }
+}
@@ -1,5 +1,5 @@
-use crate::chunk::Chunk;
-use crate::utils::{checked_split_at, Error, Result as R};
+use crate::chunk::{Chunk, ChunkMut, ChunkRef};
+use crate::utils::{checked_split_at, ByteStats, Error, Limit, Result as R};
 use std::fmt::Display;
 use std::fs::{read};
 use std::path::PathBuf;
@@ -8,10 +8,11 @@ use std::str::FromStr;
 pub struct Png {
     header: [u8; 8],    // PNG signature
     chunks: Vec<Chunk>, // Chunks representing the png structure
+    trailer: Vec<u8>,   // Raw bytes after IEND which are not chunks (polyglots, appended payloads)
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10]; // Standard header
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10]; // Standard header
 
     #[allow(unused)]
     ///From chunks (`Vec`) constructor
@@ -19,30 +20,122 @@ impl Png {
         Self {
             header: Self::STANDARD_HEADER,
             chunks,
+            trailer: Vec::new(),
         }
     }
+    /// Parsing with non-default options (see `ParseOptions`)
+    pub fn parse(bytes: &[u8], options: &ParseOptions) -> R<Png> {
+        PngRef::parse(bytes, options).map(Png::from)
+    }
     pub fn from_file(path: PathBuf) -> R<Png> {
         let content = read(path).map_err(|_|Error::FileError)?;
         Png::try_from(content.as_slice())
     }
-    ///Adds a chunk to the internal chunk aggregate
-    pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+    ///Adds a chunk at the end of the png stream, that is right before IEND if there is one (see `Place::AfterIend` to go
+    ///past it), returning its index
+    pub fn append_chunk(&mut self, chunk: Chunk) -> R<usize> {
+        let index = self.chunks_by_type("IEND").next().map_or(self.chunks.len(), |(i, _)| i);
+        self.insert_chunk_at(index, chunk)?;
+        Ok(index)
     }
-    ///Remove a chunk of a precise type from the internal chunk aggregate
+    ///Remove the first chunk of a precise type from the internal chunk aggregate (the one `chunk_by_type` gives), with the
+    ///same placement checks as `remove_chunk_at`
     pub fn remove_chunk(&mut self, chunk_type: &str) -> R<Chunk> {
-        if self.chunk_by_type(chunk_type).is_none() {
-            return Err(Error::ChunkNotFound(chunk_type.to_string()));
-        } // Have we got this type of Chunk in our chunks aggregate?
-
-        let mut index = 0;
-        for (i, chunk) in self.chunks().iter().enumerate() {
-            if chunk.chunk_type().to_string() == chunk_type {
-                index = i;
-            }
-        } // Cumbersome, but working
+        let (index, _) = self
+            .chunks_by_type(chunk_type)
+            .next()
+            .ok_or(Error::ChunkNotFound(chunk_type.to_string()))?; // Have we got this type of Chunk in our chunks aggregate?
+        self.remove_chunk_at(index)
+    }
+    /// Inserts a chunk so that it ends up at `index` (`index == len` appends)
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> R<()> {
+        if index > self.chunks.len() {
+            return Err(Error::IndexOutOfRange(index));
+        }
+        let mut types = self.types();
+        types.insert(index, chunk.chunk_type().bytes());
+        self.check_placement(&types)?;
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+    /// Removes the chunk at `index`
+    pub fn remove_chunk_at(&mut self, index: usize) -> R<Chunk> {
+        self.check_index(index)?;
+        let mut types = self.types();
+        types.remove(index);
+        self.check_placement(&types)?;
         Ok(self.chunks.remove(index))
     }
+    /// Replaces the chunk at `index`, giving the old one back
+    pub fn replace_chunk_at(&mut self, index: usize, chunk: Chunk) -> R<Chunk> {
+        self.check_index(index)?;
+        let mut types = self.types();
+        types[index] = chunk.chunk_type().bytes();
+        self.check_placement(&types)?;
+        Ok(std::mem::replace(&mut self.chunks[index], chunk))
+    }
+    /// Moves the chunk at `from` so that it ends up at `to`
+    pub fn move_chunk(&mut self, from: usize, to: usize) -> R<()> {
+        self.check_index(from)?;
+        self.check_index(to)?;
+        let mut types = self.types();
+        let moved = types.remove(from);
+        types.insert(to, moved);
+        self.check_placement(&types)?;
+        let chunk = self.chunks.remove(from);
+        self.chunks.insert(to, chunk);
+        Ok(())
+    }
+    /// Keeps only the chunks for which `keep` is true (nothing is removed if that would break placement)
+    pub fn retain<F: FnMut(&Chunk) -> bool>(&mut self, mut keep: F) -> R<()> {
+        let kept: Vec<bool> = self.chunks.iter().map(&mut keep).collect();
+        let types: Vec<[u8; 4]> = self
+            .types()
+            .into_iter()
+            .zip(&kept)
+            .filter_map(|(t, &k)| k.then_some(t))
+            .collect();
+        self.check_placement(&types)?;
+        let mut kept = kept.into_iter();
+        self.chunks.retain(|_| kept.next().unwrap());
+        Ok(())
+    }
+    /// Mutable access to every chunk's data (types, hence placement, cannot be changed through it)
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = ChunkMut<'_>> {
+        self.chunks.iter_mut().map(ChunkMut::new)
+    }
+    /// Unchecked access to the chunks, to restore an earlier state exactly (see `Editor::undo`)
+    pub(crate) fn chunks_unchecked(&mut self) -> &mut Vec<Chunk> {
+        &mut self.chunks
+    }
+    fn check_index(&self, index: usize) -> R<()> {
+        if index < self.chunks.len() {
+            Ok(())
+        } else {
+            Err(Error::IndexOutOfRange(index))
+        }
+    }
+    fn types(&self) -> Vec<[u8; 4]> {
+        self.chunks.iter().map(|chunk| chunk.chunk_type().bytes()).collect()
+    }
+    /// An edit giving the chunk types `after` must keep IHDR first and IEND last, if they were before it
+    fn check_placement(&self, after: &[[u8; 4]]) -> R<()> {
+        let placement = |types: &[[u8; 4]]| {
+            let unique = |t: &[u8; 4]| types.iter().filter(|&x| x == t).count() == 1;
+            let ihdr_ok = types.first() == Some(b"IHDR") && unique(b"IHDR");
+            let iend_ok = types.last() == Some(b"IEND") && unique(b"IEND");
+            (ihdr_ok, iend_ok)
+        };
+        let (ihdr_before, iend_before) = placement(&self.types());
+        let (ihdr_after, iend_after) = placement(after);
+        if ihdr_before && !ihdr_after {
+            return Err(Error::InvalidPlacement("IHDR must stay the first and only IHDR chunk".to_string()));
+        }
+        if iend_before && !iend_after {
+            return Err(Error::InvalidPlacement("IEND must stay the last and only IEND chunk".to_string()));
+        }
+        Ok(())
+    }
     /// Png header
     pub fn header(&self) -> &[u8; 8] {
         &self.header
@@ -53,77 +146,292 @@ impl Png {
     }
     /// First occurence of a chunk of type `chunk_type`
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        for chunk in &self.chunks {
-            if &chunk.chunk_type().to_string() == chunk_type {
-                // Choice here os to cast to a string and compare directly to the furnished string chunk code
-                return Some(chunk);
-            }
+        self.chunks
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == *chunk_type) // None if not found
+    }
+    /// Every chunk of type `chunk_type`, with its index, in file order
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &'a str) -> impl Iterator<Item = (usize, &'a Chunk)> + 'a {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(move |(_, chunk)| *chunk.chunk_type() == *chunk_type)
+    }
+    /// Chunks (with their index) whose type has the reserved bit set, which is accepted when reading but only worth a warning
+    pub fn reserved_bit_violations(&self) -> impl Iterator<Item = (usize, &Chunk)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| !chunk.chunk_type().is_reserved_bit_valid())
+    }
+    /// Trailing bytes after IEND (empty if none)
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+    /// Replaces the trailing bytes after IEND (an empty `Vec` strips them); there has to be an IEND for them to follow
+    pub fn set_trailer(&mut self, trailer: Vec<u8>) -> R<()> {
+        if !trailer.is_empty() && self.chunk_by_type("IEND").is_none() {
+            return Err(Error::InvalidPlacement("a trailer has to follow an IEND chunk".to_string()));
         }
-        None // Not found
+        self.trailer = trailer;
+        Ok(())
     }
     /// The whole png file as a `Vec` of bytes
     pub fn as_bytes(&self) -> Vec<u8> {
-        let chunk_bytes = self.chunks().iter().map(|chunk| chunk.as_bytes()).flatten(); // All the bytes of all the chunks (the iterator of Vec<u8> gets flattened)
-        self.header.into_iter().chain(chunk_bytes).collect() // Chain the header and the chunk bytes, and collect
+        let chunk_bytes = self.chunks().iter().flat_map(|chunk| chunk.as_bytes()); // All the bytes of all the chunks (the iterator of Vec<u8> gets flattened)
+        self.header.into_iter().chain(chunk_bytes).chain(self.trailer.iter().copied()).collect() // Chain the header, the chunk bytes and the trailer, and collect
+    }
+    /// Borrowed view, with the CRC each chunk is written with
+    pub fn as_png_ref(&self) -> PngRef<'_> {
+        PngRef {
+            header: self.header,
+            chunks: self.chunks.iter().map(ChunkRef::from).collect(),
+            trailer: &self.trailer,
+        }
     }
 }
 
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
+    /// Never panics: any malformed input is reported as an `Error::Parse` holding the byte offset and chunk index where parsing stopped
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        let mut chunks = Vec::new();
+        PngRef::try_from(bytes).map(Png::from) // Parsed as a borrowed view, then copied
+    }
+}
+
+/// How lenient parsing is, and how much it may take (exceeding a limit is an `Error::LimitExceeded`)
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub preserve_raw: bool, // Keep chunks with a bad CRC or non-conforming type bytes as read, so that the file round-trips bit for bit
+    pub max_chunk_size: u32,    // Largest data length accepted (the spec caps it at 2^31-1)
+    pub max_chunks: usize,      // Largest number of chunks accepted
+    pub max_allocation: usize,  // Largest cumulated data length accepted
+    pub max_decompressed: usize, // Largest inflated length of a compressed text chunk (zTXt, iTXt) decoded
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            preserve_raw: false,
+            max_chunk_size: Chunk::MAX_LENGTH,
+            max_chunks: usize::MAX,
+            max_allocation: usize::MAX,
+            max_decompressed: 8 << 20, // Compressed text is only read for display, unlike image data
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Checks the data length field of a chunk, before anything gets read or allocated for it
+    pub(crate) fn check_length(&self, length: u32) -> R<()> {
+        if length > self.max_chunk_size.min(Chunk::MAX_LENGTH) {
+            return Err(Error::LimitExceeded {
+                limit: Limit::ChunkSize,
+                max: self.max_chunk_size as usize,
+            });
+        }
+        Ok(())
+    }
+    /// Checks the totals once a chunk is accepted, given the number of chunks and data bytes so far
+    pub(crate) fn check_totals(&self, chunks: usize, allocated: usize) -> R<()> {
+        if chunks > self.max_chunks {
+            return Err(Error::LimitExceeded {
+                limit: Limit::ChunkCount,
+                max: self.max_chunks,
+            });
+        }
+        if allocated > self.max_allocation {
+            return Err(Error::LimitExceeded {
+                limit: Limit::Allocation,
+                max: self.max_allocation,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Borrowed counterpart of `Png`, whose chunks point into the parsed input instead of being copied
+#[derive(Debug)]
+pub struct PngRef<'a> {
+    header: [u8; 8],
+    chunks: Vec<ChunkRef<'a>>,
+    trailer: &'a [u8],
+}
+
+impl<'a> PngRef<'a> {
+    /// Png header
+    pub fn header(&self) -> &[u8; 8] {
+        &self.header
+    }
+    /// Chunk views (slice)
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+    /// First occurence of a chunk of type `chunk_type`
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&ChunkRef<'a>> {
+        self.chunks
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == *chunk_type)
+    }
+    /// Every chunk of type `chunk_type`, with its index, in file order
+    pub fn chunks_by_type<'s>(&'s self, chunk_type: &'s str) -> impl Iterator<Item = (usize, &'s ChunkRef<'a>)> + 's {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(move |(_, chunk)| *chunk.chunk_type() == *chunk_type)
+    }
+    /// Trailing bytes after IEND (empty if none)
+    pub fn trailer(&self) -> &'a [u8] {
+        self.trailer
+    }
+    /// Chunks (with their index) whose type has the reserved bit set
+    pub fn reserved_bit_violations(&self) -> impl Iterator<Item = (usize, &ChunkRef<'a>)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| !chunk.chunk_type().is_reserved_bit_valid())
+    }
+    /// Owned copy, for editing
+    pub fn to_png(&self) -> Png {
+        Png {
+            header: self.header,
+            chunks: self.chunks.iter().map(ChunkRef::to_chunk).collect(),
+            trailer: self.trailer.to_vec(),
+        }
+    }
+}
+
+impl From<PngRef<'_>> for Png {
+    fn from(png: PngRef<'_>) -> Self {
+        png.to_png()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = Error;
+
+    /// Never panics: any malformed input is reported as an `Error::Parse` holding the byte offset and chunk index where parsing stopped
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::parse(bytes, &ParseOptions::default())
+    }
+}
 
-        let (header, _) =
-            checked_split_at(bytes, Self::STANDARD_HEADER.len()).ok_or(Error::PngSliceError)?;
+impl<'a> PngRef<'a> {
+    /// Parsing with non-default options (see `ParseOptions`), same error reporting as `try_from`
+    pub fn parse(bytes: &'a [u8], options: &ParseOptions) -> R<Self> {
+        let mut chunks = Vec::new();
+        let parse_error = |offset, chunk, source| Error::Parse {
+            offset,
+            chunk,
+            source: Box::new(source),
+        };
+
+        let (header, _) = checked_split_at(bytes, Png::STANDARD_HEADER.len())
+            .ok_or_else(|| parse_error(0, 0, Error::PngSliceError))?;
         let header = <&[u8] as TryInto<[u8; 8]>>::try_into(header).unwrap(); // This shouldn't panic
-        if !header.eq(&Self::STANDARD_HEADER) {
-            return Err(Error::InvalidPngHeader);
+        if !header.eq(&Png::STANDARD_HEADER) {
+            return Err(parse_error(0, 0, Error::InvalidPngHeader));
         }
 
         let mut cursor = 8; // Skip the header bytes, as already extracted
+        let mut iend_seen = false;
+        let mut trailer: &[u8] = &[];
+        let mut allocated = 0usize;
 
         while cursor < bytes.len() {
             // While we still have something to read
-            let length = u32::from_be_bytes(
-                bytes[cursor..cursor + Chunk::DATA_LEN_BYTES]
-                    .try_into()
-                    .unwrap(),
-            ) as usize
-                + Chunk::TOTAL_BYTES;
-            let chunk = Chunk::try_from(&bytes[cursor..cursor + length])?;
-            cursor += length;
-            chunks.push(chunk);
+            let remaining = &bytes[cursor..];
+            let strict = !options.preserve_raw || iend_seen; // After IEND, only well-formed chunks are not trailing data
+            match chunk_at(remaining, strict, options) {
+                Ok(chunk) => {
+                    allocated = allocated.saturating_add(chunk.data().len());
+                    options
+                        .check_totals(chunks.len() + 1, allocated)
+                        .map_err(|e| parse_error(cursor, chunks.len(), e))?;
+                    cursor += chunk.size();
+                    iend_seen |= chunk.chunk_type().bytes() == *b"IEND";
+                    chunks.push(chunk);
+                }
+                Err(_) if iend_seen => {
+                    // Even a length above the limits, since trailing bytes are never allocated as a chunk
+                    trailer = remaining; // Whatever follows IEND and is not a chunk
+                    break;
+                }
+                Err(e) => return Err(parse_error(cursor, chunks.len(), e)),
+            }
         }
-        Ok(Self { header, chunks })
+        Ok(Self { header, chunks, trailer })
     }
 }
 
-impl Display for Png {
+/// Reads the chunk starting `remaining`, checking its length against the available bytes
+fn chunk_at<'a>(remaining: &'a [u8], strict: bool, options: &ParseOptions) -> R<ChunkRef<'a>> {
+    let truncated = Error::Truncated {
+        needed: Chunk::TOTAL_BYTES,
+        available: remaining.len(),
+    };
+    let (length, _) = checked_split_at(remaining, Chunk::DATA_LEN_BYTES).ok_or(truncated)?;
+    let length = u32::from_be_bytes(length.try_into().unwrap());
+    options.check_length(length)?;
+    let length = (length as usize)
+        .checked_add(Chunk::TOTAL_BYTES)
+        .ok_or(Error::ChunkSliceSizeError)?; // Only overflows on 32-bit targets
+    if length > remaining.len() {
+        return Err(Error::Truncated {
+            needed: length,
+            available: remaining.len(),
+        });
+    }
+    let raw = &remaining[..length];
+    if strict {
+        ChunkRef::try_from(raw)
+    } else {
+        ChunkRef::from_raw(raw)
+    }
+}
+
+impl Display for PngRef<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Header: {:?}", self.header())?;
         for (c, chunk) in self.chunks().iter().enumerate() {
             writeln!(f, "Chunk {c}: {chunk}")?;
         }
+        if !self.trailer().is_empty() {
+            writeln!(f, "{}", trailer_summary(&ByteStats::of(self.trailer())))?;
+        }
+        for (c, chunk) in self.reserved_bit_violations() {
+            writeln!(f, "Warning: chunk {c} ({}) has its reserved bit set", chunk.chunk_type())?;
+        }
         Ok(())
     }
 }
 
+impl Display for Png {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_png_ref().fmt(f)
+    }
+}
+
+/// One line description of trailing data
+pub fn trailer_summary(trailer: &ByteStats) -> String {
+    format!("Trailer: {} bytes after IEND (entropy {:.2} bits/byte)", trailer.len(), trailer.entropy())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
+    use crate::placement::Place;
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -224,7 +532,7 @@ mod tests {
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
@@ -233,12 +541,82 @@ mod tests {
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         png.remove_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a second one").unwrap()).unwrap();
+        let found: Vec<usize> = png.chunks_by_type("miDl").map(|(i, _)| i).collect();
+        assert_eq!(found, vec![1, 3]);
+
+        let removed = png.remove_chunk("miDl").unwrap(); // Same chunk as `chunk_by_type` gives
+        assert_eq!(&removed.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(&png.chunk_by_type("miDl").unwrap().data_as_string().unwrap(), "I am a second one");
+
+        let png_ref = PngRef::try_from(PNG_FILE.as_ref()).unwrap();
+        assert_eq!(png_ref.chunks_by_type("IDAT").count(), 1);
+    }
+
+    #[test]
+    fn test_index_editing() {
+        let mut png = testing_png();
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Inserted").unwrap()).unwrap();
+        assert_eq!(types(&png), vec!["FrSt", "TeSt", "miDl", "LASt"]);
+
+        let old = png.replace_chunk_at(0, chunk_from_strings("NeWw", "Replaced").unwrap()).unwrap();
+        assert_eq!(&old.chunk_type().to_string(), "FrSt");
+
+        png.move_chunk(0, 3).unwrap();
+        assert_eq!(types(&png), vec!["TeSt", "miDl", "LASt", "NeWw"]);
+
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&removed.chunk_type().to_string(), "miDl");
+
+        png.retain(|c| *c.chunk_type() != *"LASt").unwrap();
+        assert_eq!(types(&png), vec!["TeSt", "NeWw"]);
+
+        assert!(matches!(png.remove_chunk_at(2), Err(Error::IndexOutOfRange(2))));
+        assert!(png.insert_chunk_at(2, chunk_from_strings("EnDd", "").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_editing_keeps_ihdr_and_iend_placement() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let last = png.chunks().len() - 1;
+        let iend = || chunk_from_strings("IEND", "").unwrap();
+
+        assert!(matches!(png.remove_chunk_at(0), Err(Error::InvalidPlacement(_))));
+        assert!(png.insert_chunk_at(0, chunk_from_strings("TeSt", "").unwrap()).is_err());
+        assert!(png.insert_chunk_at(last + 1, chunk_from_strings("TeSt", "").unwrap()).is_err());
+        assert!(png.insert_chunk_at(1, iend()).is_err());
+        assert!(png.move_chunk(last, 1).is_err());
+        assert!(png.replace_chunk_at(0, chunk_from_strings("TeSt", "").unwrap()).is_err());
+        assert!(png.retain(|c| *c.chunk_type() != *"IEND").is_err());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec()); // Nothing changed
+
+        png.insert_chunk_at(last, chunk_from_strings("TeSt", "").unwrap()).unwrap();
+        assert_eq!(&png.chunks()[last].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_chunks_mut() {
+        let mut png = testing_png();
+        for mut chunk in png.chunks_mut() {
+            let data = chunk.data().to_ascii_uppercase();
+            chunk.set_data(data);
+        }
+        assert_eq!(png.chunks()[0].data(), b"I AM THE FIRST CHUNK");
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -253,6 +631,128 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_truncated_file_is_error() {
+        let cut = PNG_FILE.len() - 10; // Inside the IEND chunk
+        let png = Png::try_from(&PNG_FILE[..cut]);
+        match png {
+            Err(Error::Parse { offset, chunk, source }) => {
+                assert_eq!(offset, PNG_FILE.len() - 12); // Start of IEND
+                assert_eq!(chunk, 6); // IHDR, sRGB, gAMA, pHYs, IDAT and RuSt before it
+                assert!(matches!(*source, Error::Truncated { needed: 12, available: 2 }));
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_hostile_length_is_error() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes()); // IHDR length field
+        let png = Png::try_from(bytes.as_ref());
+        assert!(matches!(png, Err(Error::Parse { offset: 8, chunk: 0, .. })));
+    }
+
+    #[test]
+    fn test_no_panic_on_any_prefix() {
+        for end in 0..PNG_FILE.len() {
+            let _ = Png::try_from(&PNG_FILE[..end]); // Prefixes ending on a chunk boundary are fine, the others must not panic
+        }
+    }
+
+    #[test]
+    fn test_reserved_bit_chunk_is_read() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("Rust", "Odd but readable").unwrap()).unwrap();
+        let png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        let violations: Vec<usize> = png.reserved_bit_violations().map(|(i, _)| i).collect();
+        assert_eq!(violations, vec![3]);
+    }
+
+    #[test]
+    fn test_png_ref() {
+        let png_ref = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        let chunk = png_ref.chunk_by_type("RuSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "hey");
+        assert_eq!(png_ref.chunks().len(), 7);
+
+        let png = png_ref.to_png();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert_eq!(png.chunk_by_type("RuSt").unwrap().crc(), chunk.crc());
+        assert_eq!(png.to_string(), png_ref.to_string());
+    }
+
+    #[test]
+    fn test_preserve_raw_round_trip() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[40] ^= 0xFF; // sRGB CRC
+        bytes[45] = b'4'; // gAMA type
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let options = ParseOptions {
+            preserve_raw: true,
+            ..Default::default()
+        };
+        let png = Png::parse(&bytes, &options).unwrap();
+        assert!(!png.chunks()[1].has_valid_crc());
+        assert_eq!(png.as_bytes(), bytes);
+        assert_eq!(png.write_to(Vec::new()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"PK\x03\x04 appended archive");
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 7);
+        assert_eq!(png.trailer(), b"PK\x03\x04 appended archive");
+        assert_eq!(png.as_bytes(), bytes);
+        assert_eq!(png.write_to(Vec::new()).unwrap(), bytes);
+
+        let mut png = png;
+        png.set_trailer(Vec::new()).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert!(testing_png().set_trailer(b"garbage".to_vec()).is_err()); // No IEND
+    }
+
+    #[test]
+    fn test_chunks_after_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.append_chunk(chunk_from_strings("TeSt", "Before IEND").unwrap()).unwrap(), 6);
+        png.insert_chunk_placed(chunk_from_strings("TeSt", "After IEND").unwrap(), Place::AfterIend).unwrap();
+        png.set_trailer(b"garbage".to_vec()).unwrap();
+        let png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        let found: Vec<usize> = png.chunks_by_type("TeSt").map(|(i, _)| i).collect();
+        assert_eq!(found, vec![6, 8]); // Around IEND
+        assert_eq!(png.trailer(), b"garbage");
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limited = |options: ParseOptions| match Png::parse(&PNG_FILE, &options) {
+            Err(Error::Parse { source, .. }) => match *source {
+                Error::LimitExceeded { limit, .. } => Some(limit),
+                _ => None,
+            },
+            _ => None,
+        };
+        let size = ParseOptions { max_chunk_size: 1000, ..Default::default() };
+        assert_eq!(limited(size), Some(Limit::ChunkSize));
+        let count = ParseOptions { max_chunks: 3, ..Default::default() };
+        assert_eq!(limited(count), Some(Limit::ChunkCount));
+        let allocation = ParseOptions { max_allocation: 4096, ..Default::default() };
+        assert_eq!(limited(allocation), Some(Limit::Allocation));
+        assert!(Png::parse(&PNG_FILE, &ParseOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_spec_length_limit() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8..12].copy_from_slice(&0x8000_0000u32.to_be_bytes()); // IHDR length above 2^31-1
+        let png = Png::try_from(bytes.as_ref());
+        assert!(matches!(png, Err(Error::Parse { source, .. }) if matches!(*source, Error::LimitExceeded { limit: Limit::ChunkSize, .. })));
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
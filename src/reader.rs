@@ -0,0 +1,307 @@
+use crate::chunk::{crc_of, Chunk};
+use crate::chunk_type::ChunkType;
+use crate::png::{ParseOptions, Png};
+use crate::utils::{ByteStats, Error, Limit, Result};
+use std::io::{ErrorKind, Read};
+
+/// Streaming png parser: checks the signature, then yields one chunk (or error) at a time, in constant memory
+/// Iteration stops after the first error, which is reported as an `Error::Parse` like for `Png::try_from`
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    offset: usize, // Bytes consumed so far
+    chunk: usize,  // Index of the next chunk
+    done: bool,
+    iend_seen: bool,
+    trailer: Vec<u8>, // Bytes read after IEND which did not make a chunk, start of the trailing data
+    options: ParseOptions,
+    allocated: usize, // Data bytes yielded so far, checked against `options.max_allocation`
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads and checks the png signature
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Same as `new`, with size limits (the `preserve_raw` option does not apply to streaming)
+    pub fn with_options(mut reader: R, options: ParseOptions) -> Result<Self> {
+        let parse_error = |source| Error::Parse {
+            offset: 0,
+            chunk: 0,
+            source: Box::new(source),
+        };
+        let mut header = [0; 8];
+        let read = read_full(&mut reader, &mut header).map_err(parse_error)?;
+        if read < header.len() {
+            return Err(parse_error(Error::PngSliceError));
+        }
+        if header != Png::STANDARD_HEADER {
+            return Err(parse_error(Error::InvalidPngHeader));
+        }
+        Ok(Self {
+            reader,
+            offset: header.len(),
+            chunk: 0,
+            done: false,
+            iend_seen: false,
+            trailer: Vec::new(),
+            options,
+            allocated: 0,
+        })
+    }
+
+    /// Byte offset of the next chunk in the input
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Trailing data after IEND: call once iteration is over, as it reads whatever is left in the input
+    pub fn into_trailer(mut self) -> Result<Vec<u8>> {
+        if self.iend_seen {
+            self.reader.read_to_end(&mut self.trailer).map_err(|_| Error::FileError)?;
+        }
+        Ok(self.trailer)
+    }
+
+    /// Length and entropy of the trailing data, read in blocks rather than kept whole (same calling rule as `into_trailer`)
+    pub fn into_trailer_stats(mut self) -> Result<ByteStats> {
+        let mut stats = ByteStats::of(&self.trailer);
+        if self.iend_seen {
+            let mut block = [0; 8192];
+            loop {
+                match read_full(&mut self.reader, &mut block)? {
+                    0 => break,
+                    read => stats.update(&block[..read]),
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Reads the next chunk, `None` on a clean end of input
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut head = [0; Chunk::DATA_LEN_BYTES + Chunk::CHUNK_TYPE_BYTES];
+        let read = read_full(&mut self.reader, &mut head)?;
+        if self.iend_seen {
+            self.trailer.extend_from_slice(&head[..read]); // In case this is not a chunk
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < head.len() {
+            return Err(Error::Truncated {
+                needed: Chunk::TOTAL_BYTES,
+                available: read,
+            });
+        }
+        let length = u32::from_be_bytes(head[..4].try_into().unwrap());
+        self.options.check_length(length)?;
+        let length = length as usize;
+        let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&head[4..]).unwrap())?;
+        self.options.check_totals(self.chunk + 1, self.allocated.saturating_add(length))?; // Before the data is read in
+
+        // Data and CRC (read through `take`, so that a hostile length does not allocate before bytes are actually there)
+        let mut rest = Vec::new();
+        let needed = length as u64 + Chunk::CRC_BYTES as u64; // u64 so that no target overflows
+        (&mut self.reader)
+            .take(needed)
+            .read_to_end(&mut rest)
+            .map_err(|_| Error::FileError)?;
+        if self.iend_seen {
+            self.trailer.extend_from_slice(&rest);
+        }
+        if (rest.len() as u64) < needed {
+            return Err(Error::Truncated {
+                needed: length.saturating_add(Chunk::TOTAL_BYTES),
+                available: head.len() + rest.len(),
+            });
+        }
+        let crc = u32::from_be_bytes(rest[length..].try_into().unwrap());
+        rest.truncate(length);
+        if crc_of(&chunk_type, &rest) != crc {
+            return Err(Error::CRC);
+        }
+        self.trailer.clear(); // A chunk after all
+        self.allocated += length;
+        self.iend_seen |= chunk_type.bytes() == *b"IEND";
+        Ok(Some(Chunk::new(chunk_type, rest)))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_chunk() {
+            Ok(Some(chunk)) => {
+                self.offset += chunk.length() as usize + Chunk::TOTAL_BYTES;
+                self.chunk += 1;
+                Some(Ok(chunk))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e @ Error::LimitExceeded { limit: Limit::ChunkCount | Limit::Allocation, .. }) if !self.iend_seen => {
+                self.done = true; // After IEND, what would go over the totals is left unread as trailing data
+                Some(Err(Error::Parse {
+                    offset: self.offset,
+                    chunk: self.chunk,
+                    source: Box::new(e),
+                }))
+            }
+            Err(e) if self.iend_seen && !matches!(e, Error::FileError) => {
+                self.done = true; // Not a chunk after IEND: trailing data starts here (input failures still get reported)
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(Error::Parse {
+                    offset: self.offset,
+                    chunk: self.chunk,
+                    source: Box::new(e),
+                }))
+            }
+        }
+    }
+}
+
+/// Fills as much of `buf` as the reader allows, returning the number of bytes read (less than asked only at end of input)
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break, // Some readers report the end of input this way
+            Err(_) => return Err(Error::FileError),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunk = |t: &str, d: &str| Chunk::new(ChunkType::from_str(t).unwrap(), d.as_bytes().to_vec());
+        Png::from_chunks(vec![
+            chunk("FrSt", "I am the first chunk"),
+            chunk("miDl", "I am another chunk"),
+            chunk("LASt", "I am the last chunk"),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_read_chunks() {
+        let bytes = testing_bytes();
+        let chunks: Vec<Chunk> = ChunkReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["FrSt", "miDl", "LASt"]);
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 0;
+        assert!(ChunkReader::new(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_stops_at_error() {
+        let mut bytes = testing_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF; // Last chunk CRC
+        let results: Vec<Result<Chunk>> = ChunkReader::new(bytes.as_slice()).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            &results[2],
+            Err(Error::Parse { offset: 70, chunk: 2, source }) if matches!(**source, Error::CRC)
+        ));
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut bytes = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())]).as_bytes();
+        bytes.extend_from_slice(b"some trailing bytes");
+        let mut reader = ChunkReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.by_ref().count(), 1);
+        assert_eq!(reader.into_trailer().unwrap(), b"some trailing bytes");
+
+        let mut reader = ChunkReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.by_ref().count(), 1);
+        let stats = reader.into_trailer_stats().unwrap();
+        assert_eq!(stats.len(), 19);
+        assert_eq!(stats.entropy(), crate::utils::entropy(b"some trailing bytes"));
+    }
+
+    #[test]
+    fn test_read_failure_after_iend() {
+        // Input which fails once the bytes it holds are read
+        struct Failing<'a>(&'a [u8]);
+        impl Read for Failing<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match self.0.read(buf)? {
+                    0 => Err(std::io::Error::other("disk failure")),
+                    n => Ok(n),
+                }
+            }
+        }
+        let bytes = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())]).as_bytes();
+        let results: Vec<Result<Chunk>> = ChunkReader::new(Failing(&bytes)).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(&results[1], Err(Error::Parse { source, .. }) if matches!(**source, Error::FileError)));
+    }
+
+    #[test]
+    fn test_allocation_checked_before_reading() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&1000u32.to_be_bytes());
+        bytes.extend_from_slice(b"ruSt12345"); // Far less data than announced
+        let options = ParseOptions { max_allocation: 100, ..Default::default() };
+        let results: Vec<Result<Chunk>> = ChunkReader::with_options(bytes.as_slice(), options).unwrap().collect();
+        assert!(matches!(
+            &results[0],
+            Err(Error::Parse { source, .. }) if matches!(**source, Error::LimitExceeded { limit: Limit::Allocation, .. })
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let bytes = testing_bytes();
+        let options = ParseOptions { max_chunks: 2, ..Default::default() };
+        let results: Vec<Result<Chunk>> = ChunkReader::with_options(bytes.as_slice(), options).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            &results[2],
+            Err(Error::Parse { source, .. }) if matches!(**source, Error::LimitExceeded { limit: Limit::ChunkCount, .. })
+        ));
+
+        let options = ParseOptions { max_chunk_size: 19, ..Default::default() };
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), options).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_truncated_and_hostile_length() {
+        let bytes = testing_bytes();
+        let mut reader = ChunkReader::new(&bytes[..bytes.len() - 3]).unwrap();
+        assert!(reader.nth(2).unwrap().is_err());
+
+        let mut hostile = bytes[..8].to_vec();
+        hostile.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, b'R', b'u', b'S', b't']);
+        let mut reader = ChunkReader::new(hostile.as_slice()).unwrap();
+        assert!(matches!(reader.next(), Some(Err(Error::Parse { .. }))));
+        assert!(reader.next().is_none());
+    }
+}
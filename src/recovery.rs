@@ -0,0 +1,240 @@
+use crate::chunk::{crc_of, Chunk};
+use crate::chunk_type::ChunkType;
+use crate::png::{ParseOptions, Png};
+use std::fmt::Display;
+
+/// What went wrong at some place of a damaged png
+#[derive(Debug, PartialEq, Eq)]
+pub enum Damage {
+    Signature,                             // The 8 bytes signature is not the png one
+    Crc { stored: u32, computed: u32 },    // Chunk kept, but its stored CRC does not match its content
+    Skipped { length: usize },             // Bytes which could not be read as a chunk (bad length or garbage), up to the next plausible chunk
+}
+
+/// A damage found while recovering, with the byte offset where it starts and the index of the chunk it concerns (or would have been)
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub offset: usize,
+    pub chunk: usize,
+    pub damage: Damage,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.damage {
+            Damage::Signature => write!(f, "Byte {}: invalid png signature", self.offset),
+            Damage::Crc { stored, computed } => write!(
+                f,
+                "Byte {}: chunk {} kept as damaged (stored CRC {stored}, computed {computed})",
+                self.offset, self.chunk
+            ),
+            Damage::Skipped { length } => write!(
+                f,
+                "Byte {}: skipped {length} unreadable bytes before chunk {}",
+                self.offset, self.chunk
+            ),
+        }
+    }
+}
+
+/// A chunk as laid out at some offset, whatever its CRC, borrowing its data until it is accepted
+struct Candidate<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    stored_crc: u32,
+    size: usize, // Total bytes taken (length, type, data and CRC)
+}
+
+impl Candidate<'_> {
+    fn computed_crc(&self) -> u32 {
+        crc_of(&self.chunk_type, self.data)
+    }
+
+    fn to_chunk(&self) -> Chunk {
+        Chunk::new(self.chunk_type, self.data.to_vec())
+    }
+}
+
+/// Reads what looks like a chunk at `offset`: a length within `max_length` and fitting in the input, and an alphabetic type
+/// (the CRC is not checked, so this costs the same whatever the length)
+fn candidate_at(bytes: &[u8], offset: usize, max_length: u32) -> Option<Candidate<'_>> {
+    let head = bytes.get(offset..offset.checked_add(Chunk::DATA_LEN_BYTES + Chunk::CHUNK_TYPE_BYTES)?)?;
+    let length = u32::from_be_bytes(head[..4].try_into().unwrap());
+    if length > max_length.min(Chunk::MAX_LENGTH) {
+        return None;
+    }
+    let length = length as usize;
+    let size = length.checked_add(Chunk::TOTAL_BYTES)?;
+    let raw = bytes.get(offset..offset.checked_add(size)?)?;
+    let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&head[4..]).unwrap()).ok()?;
+    let stored_crc = u32::from_be_bytes(raw[8 + length..].try_into().unwrap());
+    Some(Candidate {
+        chunk_type,
+        data: &raw[8..8 + length],
+        stored_crc,
+        size,
+    })
+}
+
+/// Is there a chunk with a valid CRC at `offset`? (used to resync after unreadable bytes, where only types with the reserved
+/// bit unset are worth hashing)
+fn valid_chunk_at(bytes: &[u8], offset: usize, max_length: u32) -> bool {
+    candidate_at(bytes, offset, max_length)
+        .filter(|c| c.chunk_type.is_reserved_bit_valid())
+        .is_some_and(|c| c.computed_crc() == c.stored_crc)
+}
+
+/// Does the input end, or does a plausible chunk header start, at `offset`? (used to trust the length of a chunk with a bad CRC)
+fn boundary_at(bytes: &[u8], offset: usize, max_length: u32) -> bool {
+    offset == bytes.len() || candidate_at(bytes, offset, max_length).is_some()
+}
+
+impl Png {
+    /// Lenient alternative of `try_from`, which never fails: chunks with a wrong CRC are kept, unreadable bytes are skipped
+    /// until the next chunk with a valid CRC, and every damage is reported as a `Diagnostic`.
+    /// After IEND, the first bytes which are not a chunk with a valid CRC start the trailer, as with `try_from`.
+    /// Chunks longer than `options.max_chunk_size` are treated as unreadable
+    pub fn recover(bytes: &[u8], options: &ParseOptions) -> (Png, Vec<Diagnostic>) {
+        let mut chunks = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut trailer = Vec::new();
+        let mut iend_seen = false;
+        let max_length = options.max_chunk_size;
+
+        if bytes.get(..Png::STANDARD_HEADER.len()) != Some(&Png::STANDARD_HEADER[..]) {
+            diagnostics.push(Diagnostic {
+                offset: 0,
+                chunk: 0,
+                damage: Damage::Signature,
+            });
+        }
+
+        let mut cursor = Png::STANDARD_HEADER.len();
+        while cursor < bytes.len() {
+            if iend_seen && !valid_chunk_at(bytes, cursor, max_length) {
+                trailer = bytes[cursor..].to_vec(); // Appended data, not damage
+                break;
+            }
+            if let Some(candidate) = candidate_at(bytes, cursor, max_length) {
+                let computed = candidate.computed_crc();
+                if computed == candidate.stored_crc || boundary_at(bytes, cursor + candidate.size, max_length) {
+                    if computed != candidate.stored_crc {
+                        diagnostics.push(Diagnostic {
+                            offset: cursor,
+                            chunk: chunks.len(),
+                            damage: Damage::Crc {
+                                stored: candidate.stored_crc,
+                                computed,
+                            },
+                        });
+                    }
+                    iend_seen |= candidate.chunk_type == *"IEND";
+                    chunks.push(candidate.to_chunk());
+                    cursor += candidate.size;
+                    continue;
+                }
+            }
+
+            // Nothing readable here: resync on the next chunk with a valid CRC, or give up on the rest
+            let next = (cursor + 1..bytes.len())
+                .find(|&offset| valid_chunk_at(bytes, offset, max_length))
+                .unwrap_or(bytes.len());
+            diagnostics.push(Diagnostic {
+                offset: cursor,
+                chunk: chunks.len(),
+                damage: Damage::Skipped {
+                    length: next - cursor,
+                },
+            });
+            cursor = next;
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        png.set_trailer(trailer).expect("a trailer is only kept after IEND");
+        (png, diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            chunk("FrSt", "I am the first chunk"),
+            chunk("miDl", "I am another chunk"),
+            chunk("LASt", "I am the last chunk"),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_recover_valid_png() {
+        let (png, diagnostics) = Png::recover(&testing_bytes(), &ParseOptions::default());
+        assert_eq!(png.chunks().len(), 3);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_recover_bad_crc() {
+        let mut bytes = testing_bytes();
+        let first_crc = 8 + 12 + 20 - 1; // Last byte of the first chunk
+        bytes[first_crc] ^= 0xFF;
+        let (png, diagnostics) = Png::recover(&bytes, &ParseOptions::default());
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].damage, Damage::Crc { .. }));
+        assert_eq!(diagnostics[0].chunk, 0);
+    }
+
+    #[test]
+    fn test_recover_bad_length() {
+        let mut bytes = testing_bytes();
+        bytes[8..12].copy_from_slice(&1000u32.to_be_bytes()); // First chunk length
+        let (png, diagnostics) = Png::recover(&bytes, &ParseOptions::default());
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["miDl", "LASt"]);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                offset: 8,
+                chunk: 0,
+                damage: Damage::Skipped { length: 32 }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_recover_truncated() {
+        let bytes = testing_bytes();
+        let (png, diagnostics) = Png::recover(&bytes[..bytes.len() - 5], &ParseOptions::default());
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_recover_trailer() {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", "header"), chunk("IDAT", "image data"), chunk("IEND", "")]);
+        png.set_trailer(b"PK\x03\x04 appended archive".to_vec()).unwrap();
+        let mut bytes = png.as_bytes();
+        bytes[8 + 12 + 6 - 1] ^= 0xFF; // IHDR CRC
+        let (recovered, diagnostics) = Png::recover(&bytes, &ParseOptions::default());
+        assert_eq!(recovered.trailer(), b"PK\x03\x04 appended archive");
+        assert_eq!(recovered.chunks().len(), 3);
+        assert_eq!(diagnostics.len(), 1); // The CRC only, the trailer is no damage
+    }
+
+    #[test]
+    fn test_recover_max_chunk_size() {
+        let options = ParseOptions { max_chunk_size: 19, ..Default::default() };
+        let (png, diagnostics) = Png::recover(&testing_bytes(), &options);
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["miDl", "LASt"]); // The 20 bytes first chunk is skipped
+        assert!(matches!(diagnostics[0].damage, Damage::Skipped { length: 32 }));
+    }
+}
@@ -0,0 +1,496 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::editor::Editor;
+use crate::placement::Place;
+use crate::png::{ParseOptions, Png};
+use crate::utils::{Error, Result};
+use crate::zlib;
+use std::fmt::Display;
+
+/// Keywords defined by the PNG specification, which viewers know how to show
+pub const PREDEFINED_KEYWORDS: [&str; 10] = [
+    "Title",
+    "Author",
+    "Description",
+    "Copyright",
+    "Creation Time",
+    "Software",
+    "Disclaimer",
+    "Warning",
+    "Source",
+    "Comment",
+];
+
+/// Textual metadata of a tEXt chunk: a keyword and its Latin-1 text, separated by a null byte
+/// (or of a zTXt chunk, where the text is zlib compressed after a compression method byte)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    keyword: String,
+    text: String,
+    compressed: bool, // Written as zTXt
+}
+
+impl TextChunk {
+    pub const MAX_KEYWORD_LENGTH: usize = 79;
+
+    /// Checks both parts can be written as a tEXt chunk
+    pub fn new(keyword: &str, text: &str) -> Result<Self> {
+        check_keyword(keyword)?;
+        if let Some(c) = text.chars().find(|&c| c == '\0' || c as u32 > 0xFF) {
+            return Err(Error::InvalidData(format!("text holds {c:?}, which is not Latin-1 or is a null")));
+        }
+        Ok(Self {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            compressed: false,
+        })
+    }
+
+    /// Writes the text as zTXt rather than tEXt
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Decodes tEXt or zTXt chunks (`None` for other chunk types), inflating at most `options.max_decompressed` bytes
+    pub fn from_chunk(chunk: &Chunk, options: &ParseOptions) -> Option<Result<Self>> {
+        match &chunk.chunk_type().bytes() {
+            b"tEXt" => Some(Self::parse(chunk.data())),
+            b"zTXt" => Some(Self::parse_compressed(chunk.data(), options.max_decompressed)),
+            _ => None,
+        }
+    }
+
+    /// Decodes tEXt chunk data
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let separator = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::InvalidData("tEXt has no null separator".to_string()))?;
+        TextChunk::new(&latin1(&data[..separator]), &latin1(&data[separator + 1..]))
+    }
+
+    /// Decodes zTXt chunk data, failing with `LimitExceeded` if the text inflates past `max_length` bytes
+    pub fn parse_compressed(data: &[u8], max_length: usize) -> Result<Self> {
+        let separator = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::InvalidData("zTXt has no null separator".to_string()))?;
+        match data.get(separator + 1) {
+            Some(0) => {}
+            Some(method) => return Err(Error::InvalidData(format!("zTXt compression method {method} is unknown"))),
+            None => return Err(Error::InvalidData("zTXt has no compression method".to_string())),
+        }
+        let text = zlib::decompress(&data[separator + 2..], max_length)?;
+        Ok(TextChunk::new(&latin1(&data[..separator]), &latin1(&text))?.compressed(true))
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Is the keyword one of the `PREDEFINED_KEYWORDS`?
+    pub fn is_predefined(&self) -> bool {
+        PREDEFINED_KEYWORDS.contains(&self.keyword.as_str())
+    }
+
+    /// tEXt or zTXt chunk data
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self.compressed {
+            true => [to_latin1(&self.keyword), vec![0, 0], zlib::compress(&to_latin1(&self.text))].concat(),
+            false => [to_latin1(&self.keyword), vec![0], to_latin1(&self.text)].concat(),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let chunk_type = if self.compressed { *b"zTXt" } else { *b"tEXt" };
+        Chunk::new(ChunkType::try_from(chunk_type).unwrap(), self.as_bytes())
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.keyword, self.text)
+    }
+}
+
+/// International textual metadata of an iTXt chunk: a keyword, a language tag, the keyword translated in that language
+/// and the UTF-8 text, which may be zlib compressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    keyword: String,
+    language: String,           // Such as `fr` or `en-GB`, empty when unknown
+    translated_keyword: String, // Empty when not given
+    text: String,
+    compressed: bool,
+}
+
+impl InternationalText {
+    /// Checks the keyword and language tag can be written as an iTXt chunk
+    pub fn new(keyword: &str, language: &str, translated_keyword: &str, text: &str) -> Result<Self> {
+        check_keyword(keyword)?;
+        check_language(language)?;
+        if translated_keyword.contains('\0') {
+            return Err(Error::InvalidData("iTXt translated keyword holds a null".to_string()));
+        }
+        Ok(Self {
+            keyword: keyword.to_string(),
+            language: language.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+            text: text.to_string(),
+            compressed: false,
+        })
+    }
+
+    /// Compresses the text with zlib
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Decodes iTXt chunk data, failing with `LimitExceeded` if a compressed text inflates past `max_length` bytes
+    pub fn parse(data: &[u8], max_length: usize) -> Result<Self> {
+        let invalid = |why: &str| Error::InvalidData(format!("iTXt {why}"));
+        let (keyword, rest) = split_at_null(data).ok_or(invalid("has no null after its keyword"))?;
+        let [flag, method, rest @ ..] = rest else {
+            return Err(invalid("has no compression flag and method"));
+        };
+        let (language, rest) = split_at_null(rest).ok_or(invalid("has no null after its language tag"))?;
+        let (translated_keyword, text) = split_at_null(rest).ok_or(invalid("has no null after its translated keyword"))?;
+        let text = match (flag, method) {
+            (0, _) => text.to_vec(),
+            (1, 0) => zlib::decompress(text, max_length)?,
+            (1, method) => return Err(invalid(&format!("compression method {method} is unknown"))),
+            (flag, _) => return Err(invalid(&format!("compression flag {flag} is invalid"))),
+        };
+        let utf8 = |bytes: &[u8], field: &str| {
+            String::from_utf8(bytes.to_vec()).map_err(|_| invalid(&format!("{field} is not UTF-8")))
+        };
+        let text = Self::new(
+            &latin1(keyword),
+            &utf8(language, "language tag")?,
+            &utf8(translated_keyword, "translated keyword")?,
+            &utf8(&text, "text")?,
+        )?;
+        Ok(text.compressed(*flag == 1))
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// iTXt chunk data
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let text = match self.compressed {
+            true => zlib::compress(self.text.as_bytes()),
+            false => self.text.as_bytes().to_vec(),
+        };
+        [
+            to_latin1(&self.keyword),
+            vec![0, self.compressed as u8, 0],
+            self.language.as_bytes().to_vec(),
+            vec![0],
+            self.translated_keyword.as_bytes().to_vec(),
+            vec![0],
+            text,
+        ]
+        .concat()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::try_from(*b"iTXt").unwrap(), self.as_bytes())
+    }
+}
+
+impl Display for InternationalText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.keyword)?;
+        match (self.language.as_str(), self.translated_keyword.as_str()) {
+            ("", "") => {}
+            (language, "") => write!(f, " [{language}]")?,
+            ("", translated) => write!(f, " [{translated}]")?,
+            (language, translated) => write!(f, " [{language}, {translated}]")?,
+        }
+        write!(f, ": {}", self.text)
+    }
+}
+
+/// Any textual chunk: tEXt, zTXt or iTXt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Text {
+    Latin1(TextChunk),                // tEXt or zTXt
+    International(InternationalText), // iTXt
+}
+
+impl Text {
+    /// Decodes tEXt, zTXt or iTXt chunks (`None` for other chunk types), inflating at most `options.max_decompressed` bytes
+    pub fn from_chunk(chunk: &Chunk, options: &ParseOptions) -> Option<Result<Self>> {
+        match &chunk.chunk_type().bytes() {
+            b"iTXt" => Some(InternationalText::parse(chunk.data(), options.max_decompressed).map(Text::International)),
+            _ => TextChunk::from_chunk(chunk, options).map(|text| text.map(Text::Latin1)),
+        }
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            Text::Latin1(text) => text.keyword(),
+            Text::International(text) => text.keyword(),
+        }
+    }
+
+    /// Language tag (empty for tEXt and zTXt)
+    pub fn language(&self) -> &str {
+        match self {
+            Text::Latin1(_) => "",
+            Text::International(text) => text.language(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Text::Latin1(text) => text.text(),
+            Text::International(text) => text.text(),
+        }
+    }
+
+    /// Has this keyword, and this language tag (ignoring case) when one is given
+    pub fn matches(&self, keyword: &str, language: Option<&str>) -> bool {
+        self.keyword() == keyword && language.is_none_or(|language| self.language().eq_ignore_ascii_case(language))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        match self {
+            Text::Latin1(text) => text.to_chunk(),
+            Text::International(text) => text.to_chunk(),
+        }
+    }
+}
+
+impl From<TextChunk> for Text {
+    fn from(text: TextChunk) -> Self {
+        Text::Latin1(text)
+    }
+}
+
+impl From<InternationalText> for Text {
+    fn from(text: InternationalText) -> Self {
+        Text::International(text)
+    }
+}
+
+impl Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Text::Latin1(text) => text.fmt(f),
+            Text::International(text) => text.fmt(f),
+        }
+    }
+}
+
+/// Keyword rules shared by the text chunks: 1 to 79 printable Latin-1 characters, no leading, trailing or consecutive spaces
+pub fn check_keyword(keyword: &str) -> Result<()> {
+    let invalid = |why: &str| Err(Error::InvalidData(format!("keyword {keyword:?} {why}")));
+    let length = keyword.chars().count();
+    if length == 0 || length > TextChunk::MAX_KEYWORD_LENGTH {
+        return invalid("must be 1 to 79 characters long");
+    }
+    if !keyword.chars().all(|c| matches!(c as u32, 0x20..=0x7E | 0xA1..=0xFF)) {
+        return invalid("must only hold printable Latin-1 characters");
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return invalid("has leading, trailing or consecutive spaces");
+    }
+    Ok(())
+}
+
+/// Language tag rules of iTXt: empty, or hyphen separated words of 1 to 8 ASCII letters and digits (RFC 3066)
+pub fn check_language(language: &str) -> Result<()> {
+    let word = |w: &str| (1..=8).contains(&w.len()) && w.chars().all(|c| c.is_ascii_alphanumeric());
+    if !language.is_empty() && !language.split('-').all(word) {
+        return Err(Error::InvalidData(format!("language tag {language:?} must be words of 1 to 8 letters or digits")));
+    }
+    Ok(())
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn to_latin1(s: &str) -> Vec<u8> {
+    s.chars().map(|c| c as u8).collect() // Checked on creation
+}
+
+fn split_at_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let separator = data.iter().position(|&b| b == 0)?;
+    Some((&data[..separator], &data[separator + 1..]))
+}
+
+impl Png {
+    /// Every tEXt, zTXt and iTXt chunk with its index, decoded within the `options` limits
+    pub fn texts<'a>(&'a self, options: &'a ParseOptions) -> impl Iterator<Item = (usize, Result<Text>)> + 'a {
+        self.chunks().iter().enumerate().filter_map(|(i, chunk)| Text::from_chunk(chunk, options).map(|text| (i, text)))
+    }
+
+    /// First valid text chunk with this keyword (and language tag when one is given), with its index
+    pub fn find_text(&self, keyword: &str, language: Option<&str>, options: &ParseOptions) -> Option<(usize, Text)> {
+        self.texts(options)
+            .filter_map(|(i, text)| text.ok().map(|text| (i, text)))
+            .find(|(_, text)| text.matches(keyword, language))
+    }
+
+    /// Replaces the first text chunk with the same keyword and language tag, or adds one before IEND, returning its index
+    pub fn set_text(&mut self, text: impl Into<Text>, options: &ParseOptions) -> Result<usize> {
+        let text = text.into();
+        match self.find_text(text.keyword(), Some(text.language()), options) {
+            Some((index, _)) => {
+                self.replace_chunk_at(index, text.to_chunk())?;
+                Ok(index)
+            }
+            None => self.insert_chunk_placed(text.to_chunk(), Place::BeforeIend),
+        }
+    }
+
+    /// Removes every text chunk with this keyword (and language tag when one is given), returning how many were
+    pub fn remove_text(&mut self, keyword: &str, language: Option<&str>, options: &ParseOptions) -> Result<usize> {
+        let before = self.chunks().len();
+        self.retain(|chunk| Text::from_chunk(chunk, options).is_none_or(|text| text.map_or(true, |t| !t.matches(keyword, language))))?;
+        Ok(before - self.chunks().len())
+    }
+}
+
+impl Editor {
+    /// `Png::set_text`, as an undoable operation
+    pub fn set_text(&mut self, text: impl Into<Text>, options: &ParseOptions) -> Result<usize> {
+        let text = text.into();
+        let existing = self.png().find_text(text.keyword(), Some(text.language()), options).map(|(i, _)| i);
+        match existing {
+            Some(index) => self.replace(index, text.to_chunk()).map(|_| index),
+            None => self.insert_placed(text.to_chunk(), Place::BeforeIend),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let chunk = |t: &str| Chunk::new(ChunkType::from_str(t).unwrap(), Vec::new());
+        Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")])
+    }
+
+    #[test]
+    fn test_keyword_rules() {
+        assert!(TextChunk::new("Title", "A picture").is_ok());
+        assert!(TextChunk::new("Créé par", "Latin-1 is fine").is_ok());
+        for keyword in ["", " Title", "Title ", "Two  spaces", "Ti\ttle", "Ŧitle", &"k".repeat(80)] {
+            assert!(TextChunk::new(keyword, "text").is_err(), "{keyword:?}");
+        }
+        assert!(TextChunk::new("Title", "emoji 🦀").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = TextChunk::new("Comment", "Déjà vu\nsecond line").unwrap();
+        assert_eq!(text.as_bytes().len(), 7 + 1 + 19);
+        assert_eq!(TextChunk::parse(&text.as_bytes()).unwrap(), text);
+        assert!(text.is_predefined());
+        assert!(TextChunk::parse(b"no separator").is_err());
+    }
+
+    #[test]
+    fn test_png_helpers() {
+        let options = ParseOptions::default();
+        let mut png = testing_png();
+        assert_eq!(png.set_text(TextChunk::new("Title", "first").unwrap(), &options).unwrap(), 2);
+        assert_eq!(png.set_text(TextChunk::new("Author", "me").unwrap(), &options).unwrap(), 3);
+        assert_eq!(png.set_text(TextChunk::new("Title", "second").unwrap(), &options).unwrap(), 2);
+        assert_eq!(png.find_text("Title", None, &options).unwrap().1.text(), "second");
+        assert_eq!(png.texts(&options).count(), 2);
+        assert_eq!(png.remove_text("Title", None, &options).unwrap(), 1);
+        assert!(png.find_text("Title", None, &options).is_none());
+        let compressed = TextChunk::new("Title", "third").unwrap().compressed(true);
+        assert_eq!(png.set_text(compressed.clone(), &options).unwrap(), 3);
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "zTXt");
+        assert_eq!(png.find_text("Title", None, &options).unwrap().1, Text::from(compressed));
+        assert_eq!(png.remove_text("Title", None, &options).unwrap(), 1);
+
+        let mut editor = Editor::new(testing_png(), "text.png".into());
+        assert_eq!(editor.set_text(TextChunk::new("Title", "first").unwrap(), &options).unwrap(), 2);
+        assert_eq!(editor.set_text(TextChunk::new("Title", "second").unwrap(), &options).unwrap(), 2);
+        editor.undo();
+        assert_eq!(editor.png().find_text("Title", None, &options).unwrap().1.text(), "first");
+    }
+
+    #[test]
+    fn test_languages() {
+        let options = ParseOptions::default();
+        let mut png = testing_png();
+        png.set_text(TextChunk::new("Title", "A cat").unwrap(), &options).unwrap();
+        png.set_text(InternationalText::new("Title", "fr", "Titre", "Un chat").unwrap(), &options).unwrap();
+        png.set_text(InternationalText::new("Title", "ja", "タイトル", "猫").unwrap(), &options).unwrap();
+        png.set_text(InternationalText::new("Title", "FR", "Titre", "Une chatte").unwrap(), &options).unwrap();
+        assert_eq!(png.texts(&options).count(), 3); // One per language, tags ignoring case
+        assert_eq!(png.find_text("Title", Some("fr"), &options).unwrap().1.text(), "Une chatte");
+        assert_eq!(png.find_text("Title", Some(""), &options).unwrap().1.text(), "A cat");
+        assert_eq!(png.remove_text("Title", Some("ja"), &options).unwrap(), 1);
+        assert_eq!(png.remove_text("Title", None, &options).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_compressed() {
+        let long = "a long comment, ".repeat(100);
+        let text = TextChunk::new("Comment", &long).unwrap().compressed(true);
+        let chunk = text.to_chunk();
+        assert!(chunk.data().len() < 100);
+        assert_eq!(TextChunk::from_chunk(&chunk, &ParseOptions::default()).unwrap().unwrap(), text);
+        let options = ParseOptions { max_decompressed: 1000, ..Default::default() };
+        assert!(matches!(TextChunk::from_chunk(&chunk, &options), Some(Err(Error::LimitExceeded { .. }))));
+        let mut method = chunk.data().to_vec();
+        method[8] = 1;
+        assert!(TextChunk::parse_compressed(&method, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_international_text() {
+        let text = InternationalText::new("Description", "en-GB", "", "Ünïcode ✓\nwith a second line").unwrap();
+        assert_eq!(text.to_string(), "Description [en-GB]: Ünïcode ✓\nwith a second line");
+        for text in [text.clone(), text.compressed(true)] {
+            let chunk = Text::from(text.clone()).to_chunk();
+            assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+            assert_eq!(Text::from_chunk(&chunk, &ParseOptions::default()).unwrap().unwrap(), Text::from(text));
+        }
+        assert_eq!(InternationalText::parse(b"Title\0\0\0x-klingon\0\0Qapla'", 100).unwrap().language(), "x-klingon");
+        assert!(InternationalText::parse(b"Title\0\0\0en\0\0\xff", 100).is_err()); // Not UTF-8
+        assert!(InternationalText::parse(b"Title\0\x02\0en\0\0text", 100).is_err()); // Compression flag
+        assert!(InternationalText::parse(b"Title\0\0\0en", 100).is_err()); // Truncated
+        for language in ["en_GB", "toolongtag", "en-", "é"] {
+            assert!(InternationalText::new("Title", language, "", "text").is_err(), "{language:?}");
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::editor::Editor;
+use crate::placement::Place;
+use crate::png::Png;
+use crate::utils::{Error, Result};
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Time of the last image modification, in UTC, decoded from the tIME chunk data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ImageTime {
+    pub year: u16,  // Complete year, such as 1995
+    pub month: u8,  // 1 to 12
+    pub day: u8,    // 1 to 31
+    pub hour: u8,   // 0 to 23
+    pub minute: u8, // 0 to 59
+    pub second: u8, // 0 to 60, for leap seconds
+}
+
+impl ImageTime {
+    pub const LENGTH: usize = 7; // Data bytes of a tIME chunk
+
+    /// Checks every field is in range, days against the length of the month
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self> {
+        let invalid = |why: String| Err(Error::InvalidData(format!("tIME {why}")));
+        if !(1..=12).contains(&month) {
+            return invalid(format!("month {month} is out of range"));
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return invalid(format!("day {day} is out of range for {} {year}", MONTHS[month as usize - 1]));
+        }
+        if hour > 23 || minute > 59 || second > 60 {
+            return invalid(format!("time {hour:02}:{minute:02}:{second:02} is out of range"));
+        }
+        Ok(Self { year, month, day, hour, minute, second })
+    }
+
+    /// Time `seconds` after the Unix epoch
+    pub fn from_unix(seconds: u64) -> Self {
+        let (days, seconds) = (seconds / 86400, seconds % 86400);
+        let (year, month, day) = civil_from_days(days as i64);
+        Self {
+            year: year as u16,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    /// Current time, as given by the system clock
+    pub fn now() -> Self {
+        Self::from_unix(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+    }
+
+    /// Day of the week, 0 being Sunday
+    pub fn weekday(&self) -> u8 {
+        (days_from_civil(self.year as i64, self.month, self.day) + 4).rem_euclid(7) as u8 // The epoch was a Thursday
+    }
+
+    /// tIME chunk data
+    pub fn as_bytes(&self) -> [u8; Self::LENGTH] {
+        let [high, low] = self.year.to_be_bytes();
+        [high, low, self.month, self.day, self.hour, self.minute, self.second]
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::try_from(*b"tIME").unwrap(), self.as_bytes().to_vec())
+    }
+}
+
+/// Decodes and checks tIME chunk data
+impl TryFrom<&[u8]> for ImageTime {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let &[high, low, month, day, hour, minute, second] = bytes else {
+            return Err(Error::InvalidData(format!(
+                "tIME is {} bytes long instead of {}",
+                bytes.len(),
+                Self::LENGTH
+            )));
+        };
+        Self::new(u16::from_be_bytes([high, low]), month, day, hour, minute, second)
+    }
+}
+
+/// Such as "Tuesday 5 March 2024, 14:07:09 UTC"
+impl Display for ImageTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}, {:02}:{:02}:{:02} UTC",
+            WEEKDAYS[self.weekday() as usize],
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since the Unix epoch and proleptic Gregorian dates, counting years from March so that
+// leap days come last (see http://howardhinnant.github.io/date_algorithms.html)
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468; // From 0000-03-01
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // 0 is March
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u8;
+    (year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = year - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let shifted_month = (month as i64 + 9) % 12; // 0 is March
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+impl Png {
+    /// Decoded tIME chunk (`ChunkNotFound` if there is none)
+    pub fn image_time(&self) -> Result<ImageTime> {
+        let time = self.chunk_by_type("tIME").ok_or(Error::ChunkNotFound("tIME".to_string()))?;
+        ImageTime::try_from(time.data())
+    }
+}
+
+impl Editor {
+    /// Updates the tIME chunk, or adds one before IEND (as an undoable operation)
+    pub fn touch(&mut self, time: ImageTime) -> Result<()> {
+        let existing = self.png().chunks_by_type("tIME").next().map(|(i, _)| i);
+        match existing {
+            Some(index) => self.replace(index, time.to_chunk()),
+            None => self.insert_placed(time.to_chunk(), Place::BeforeIend).map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_validation() {
+        assert!(ImageTime::new(2024, 2, 29, 23, 59, 60).is_ok()); // Leap day and leap second
+        assert!(ImageTime::new(2023, 2, 29, 0, 0, 0).is_err());
+        assert!(ImageTime::new(1900, 2, 29, 0, 0, 0).is_err());
+        assert!(ImageTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+        assert!(ImageTime::new(2024, 4, 31, 0, 0, 0).is_err());
+        assert!(ImageTime::new(2024, 13, 1, 0, 0, 0).is_err());
+        assert!(ImageTime::new(2024, 1, 1, 24, 0, 0).is_err());
+        assert!(ImageTime::try_from(&[7, 232, 1, 1, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn test_unix_and_display() {
+        let time = ImageTime::from_unix(1_709_647_629);
+        assert_eq!(time, ImageTime::new(2024, 3, 5, 14, 7, 9).unwrap());
+        assert_eq!(time.to_string(), "Tuesday 5 March 2024, 14:07:09 UTC");
+        assert_eq!(ImageTime::from_unix(0).to_string(), "Thursday 1 January 1970, 00:00:00 UTC");
+        assert_eq!(ImageTime::from_unix(951_782_400), ImageTime::new(2000, 2, 29, 0, 0, 0).unwrap());
+        assert_eq!(ImageTime::try_from(&time.as_bytes()[..]).unwrap(), time);
+    }
+
+    #[test]
+    fn test_touch() {
+        let chunk = |t: &str| Chunk::new(ChunkType::from_str(t).unwrap(), Vec::new());
+        let png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]);
+        let mut editor = Editor::new(png, "touched.png".into());
+        editor.touch(ImageTime::from_unix(0)).unwrap();
+        assert_eq!(editor.png().chunks()[2].chunk_type().to_string(), "tIME");
+        editor.touch(ImageTime::from_unix(86400)).unwrap();
+        assert_eq!(editor.png().chunks().len(), 4); // Updated, not added again
+        assert_eq!(editor.png().image_time().unwrap().day, 2);
+        editor.undo();
+        assert_eq!(editor.png().image_time().unwrap().day, 1);
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Display;
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names, clippy::upper_case_acronyms)]
 pub enum Error {
     //ChunkType
     CodeLengthError(usize), // Invalid length (furnished length)
@@ -15,7 +16,16 @@ pub enum Error {
     //Png
     ChunkNotFound(String), // When searching for a specific chunk with the chunktype, found nothing
     InvalidPngHeader, //When header is not correct when building from byutes slice
+    Truncated { needed: usize, available: usize }, // Input ends before the chunk announced by its length field does
+    Parse { offset: usize, chunk: usize, source: Box<Error> }, // Where (byte offset and chunk index) and why parsing a png stopped
     FileError,     // When IO operations fail
+    MissingIend,   // A png was written without its IEND chunk
+    LimitExceeded { limit: Limit, max: usize }, // A parsing or writing limit was exceeded (see `ParseOptions`)
+    IndexOutOfRange(usize), // No chunk at this index
+    InvalidPlacement(String), // An edit would break IHDR or IEND placement
+    NonConforming(usize), // Png breaks the specification (number of errors found by the validator)
+    //Chunk contents
+    InvalidData(String), // A standard chunk could not be decoded (which chunk and why)
 }
 
 impl Display for Error {
@@ -26,15 +36,53 @@ impl Display for Error {
             Error::CRC => "Invalid CRC".to_string(),
             Error::ChunkNotFound(typ) => format!("No chunk found for the chunktype {typ}"),
             Error::FileError => "IO error occured".to_string(),
+            Error::MissingIend => "Png was written without an IEND chunk".to_string(),
+            Error::LimitExceeded { limit, max } => format!("{limit} limit exceeded (max {max})"),
+            Error::IndexOutOfRange(i) => format!("No chunk at index {i}"),
+            Error::InvalidPlacement(why) => format!("Invalid chunk placement: {why}"),
+            Error::NonConforming(n) => format!("Png is not conforming ({n} errors found)"),
+            Error::InvalidData(why) => format!("Invalid chunk data: {why}"),
             Error::InvalidType => "Invalid Chunktype detected".to_string(),
             Error::InvalidChar => "Invalid char in type-code".to_string(),
             Error::InvalidPngHeader => "Png contains invalid header".to_string(),
             Error::PngSliceError => "Error while creating a png from a bad bytes sequence".to_string(),
+            Error::Truncated { needed, available } => format!("Input is truncated ({needed} bytes needed, {available} available)"),
+            Error::Parse { offset, chunk, source } => format!("Parsing stopped at byte {offset} (chunk {chunk}): {}", source.to_string().trim_end()),
         };
         writeln!(f, "{message}")
     }
 }
 
+/// Which limit an `Error::LimitExceeded` is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    ChunkSize,  // Data length of a single chunk
+    ChunkCount, // Number of chunks
+    Allocation, // Cumulated data length of all chunks
+    Decompression, // Inflated length of compressed chunk data
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Limit::ChunkSize => "Chunk size",
+            Limit::ChunkCount => "Chunk count",
+            Limit::Allocation => "Allocation",
+            Limit::Decompression => "Decompression",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
 /// Checked alternative of split_at()
 pub fn checked_split_at<T>(slice: &[T], mid: usize) -> Option<(&[T], &[T])> {
     if mid < slice.len() {
@@ -43,3 +91,58 @@ pub fn checked_split_at<T>(slice: &[T], mid: usize) -> Option<(&[T], &[T])> {
         None
     }
 }
+
+/// Shannon entropy of a byte sequence, in bits per byte (0 for empty or constant data, 8 for uniformly random data)
+pub fn entropy(bytes: &[u8]) -> f64 {
+    ByteStats::of(bytes).entropy()
+}
+
+/// Length and byte histogram of a byte sequence, which can be fed piece by piece (so that streams need not be kept in memory)
+#[derive(Debug, Clone)]
+pub struct ByteStats {
+    length: usize,
+    counts: [usize; 256],
+}
+
+impl Default for ByteStats {
+    fn default() -> Self {
+        Self { length: 0, counts: [0; 256] }
+    }
+}
+
+impl ByteStats {
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut stats = Self::default();
+        stats.update(bytes);
+        stats
+    }
+
+    /// Counts more bytes of the sequence
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.counts[b as usize] += 1;
+        }
+        self.length += bytes.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Shannon entropy of the bytes counted so far, in bits per byte
+    pub fn entropy(&self) -> f64 {
+        let total = self.length as f64;
+        self.counts
+            .iter()
+            .filter(|&&n| n > 0)
+            .map(|&n| {
+                let p = n as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,272 @@
+use crate::known_chunk::KnownChunk;
+use crate::palette::Transparency;
+use crate::png::Png;
+use std::fmt::Display;
+
+/// How bad a spec violation is: an `Error` makes the file a non-conforming png, a `Warning` does not
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A spec violation, with the index of the chunk it was found at (`None` when it concerns the whole file)
+#[derive(Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub chunk: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.chunk {
+            Some(c) => write!(f, "{severity}: chunk {c}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+impl Png {
+    /// Checks the chunk sequence against the PNG specification, reporting every violation found
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        let mut error = |chunk, message: String| issues.push(Issue { severity: Severity::Error, chunk, message });
+        let knowns: Vec<Option<KnownChunk>> = self.chunks().iter().map(|c| c.chunk_type().known()).collect();
+        let mut first = [None; KnownChunk::ALL.len()]; // First index of each known chunk, `ALL` being in declaration order
+        for (i, known) in knowns.iter().enumerate().rev() {
+            if let Some(known) = known {
+                first[*known as usize] = Some(i);
+            }
+        }
+        let position = |known: KnownChunk| first[known as usize];
+        let last = |known: KnownChunk| knowns.iter().rposition(|k| *k == Some(known));
+        let (first_idat, last_idat) = (position(KnownChunk::Idat), last(KnownChunk::Idat));
+        let plte = position(KnownChunk::Plte);
+        let starts_with_ihdr = knowns.first() == Some(&Some(KnownChunk::Ihdr));
+
+        // IHDR
+        if knowns.is_empty() {
+            error(None, "png has no chunks".to_string());
+        } else if !starts_with_ihdr {
+            error(Some(0), "first chunk is not IHDR".to_string());
+        }
+
+        // IEND
+        match last(KnownChunk::Iend) {
+            Some(i) if i != knowns.len() - 1 => error(Some(i + 1), "chunk found after IEND".to_string()),
+            Some(_) => {}
+            None => error(None, "IEND chunk is missing".to_string()),
+        }
+
+        // IDAT
+        match first_idat.zip(last_idat) {
+            Some((first, last)) => {
+                if let Some(gap) = (first..last).find(|&i| knowns[i] != Some(KnownChunk::Idat)) {
+                    error(Some(gap), "IDAT chunks are not consecutive".to_string());
+                }
+            }
+            None => error(None, "IDAT chunk is missing".to_string()),
+        }
+
+        // Colour type dependant chunks
+        let ihdr = self.ihdr();
+        if let (Err(e), true) = (&ihdr, starts_with_ihdr) {
+            error(Some(0), e.to_string().trim_end().to_string());
+        }
+        if let Ok(ihdr) = ihdr {
+            let colour_type = ihdr.colour_type as u8;
+            for known in KnownChunk::ALL {
+                if known.required_colour_types().contains(&colour_type) && position(known).is_none() {
+                    error(None, format!("{known} chunk is required for colour type {colour_type}"));
+                }
+                if let Some(p) = position(known).filter(|_| !known.allowed_colour_types().contains(&colour_type)) {
+                    error(Some(p), format!("{known} chunk is forbidden for colour type {colour_type}"));
+                }
+            }
+
+            // Palette and transparency contents
+            let palette = self.palette();
+            if let (Some(p), Err(e)) = (plte, &palette) {
+                error(Some(p), e.to_string().trim_end().to_string());
+            }
+            let trns = position(KnownChunk::Trns).filter(|_| KnownChunk::Trns.allowed_colour_types().contains(&colour_type));
+            if let Some(t) = trns {
+                if let Err(e) = Transparency::parse(self.chunks()[t].data(), &ihdr, palette.as_ref().ok()) {
+                    error(Some(t), e.to_string().trim_end().to_string());
+                }
+            }
+        }
+
+        // Every chunk on its own: multiplicity, ordering and type bits
+        for (i, (chunk, known)) in self.chunks().iter().zip(&knowns).enumerate() {
+            let t = chunk.chunk_type();
+            let Some(known) = *known else {
+                if t.is_critical() {
+                    error(Some(i), format!("unknown critical chunk {t}"));
+                }
+                continue;
+            };
+            if !known.multiple_allowed() && position(known) != Some(i) {
+                error(Some(i), format!("duplicate {t} chunk"));
+            }
+            if known.must_precede_plte() && plte.is_some_and(|p| i > p) {
+                error(Some(i), format!("{t} chunk must precede PLTE"));
+            }
+            if known.must_follow_plte() && plte.is_some_and(|p| i < p) {
+                error(Some(i), format!("{t} chunk must follow PLTE"));
+            }
+            if known.must_precede_idat() && first_idat.is_some_and(|first| i > first) {
+                error(Some(i), format!("{t} chunk must precede IDAT"));
+            }
+            if known.must_follow_idat() && last_idat.is_some_and(|last| i < last) {
+                error(Some(i), format!("{t} chunk must follow IDAT"));
+            }
+        }
+
+        for (i, chunk) in self.reserved_bit_violations() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                chunk: Some(i),
+                message: format!("{} chunk has its reserved bit set", chunk.chunk_type()),
+            });
+        }
+        if !self.trailer().is_empty() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                chunk: None,
+                message: format!("{} bytes of trailing data after IEND", self.trailer().len()),
+            });
+        }
+        if position(KnownChunk::Iccp).is_some() && position(KnownChunk::Srgb).is_some() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                chunk: position(KnownChunk::Srgb),
+                message: "iCCP and sRGB chunks should not both be present".to_string(),
+            });
+        }
+
+        issues
+    }
+
+    /// Is the png conforming (no `Error` severity issue)?
+    pub fn is_conforming(&self) -> bool {
+        self.validate().iter().all(|issue| issue.severity != Severity::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn ihdr(colour_type: u8) -> Chunk {
+        chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, colour_type, 0, 0, 0])
+    }
+
+    fn errors(png: &Png) -> Vec<Option<usize>> {
+        png.validate()
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.chunk)
+            .collect()
+    }
+
+    #[test]
+    fn test_minimal_png_is_conforming() {
+        let png = Png::from_chunks(vec![ihdr(0), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert!(png.validate().is_empty());
+        assert!(png.is_conforming());
+    }
+
+    #[test]
+    fn test_ihdr_and_iend_placement() {
+        let png = Png::from_chunks(vec![chunk("IDAT", b""), ihdr(0), chunk("IEND", b""), chunk("ruSt", b"")]);
+        assert_eq!(errors(&png), vec![Some(0), Some(3), Some(1)]); // IHDR not first, chunk after IEND, IHDR after IDAT
+    }
+
+    #[test]
+    fn test_idat_not_consecutive() {
+        let png = Png::from_chunks(vec![
+            ihdr(0),
+            chunk("IDAT", b""),
+            chunk("tEXt", b"a\0b"),
+            chunk("IDAT", b""),
+            chunk("IEND", b""),
+        ]);
+        assert_eq!(errors(&png), vec![Some(2)]);
+    }
+
+    #[test]
+    fn test_palette_rules() {
+        let missing = Png::from_chunks(vec![ihdr(3), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&missing), vec![None]);
+        let forbidden = Png::from_chunks(vec![ihdr(0), chunk("PLTE", &[0; 3]), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&forbidden), vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_palette_contents() {
+        let png = Png::from_chunks(vec![
+            ihdr(3),
+            chunk("PLTE", &[0; 4]),
+            chunk("tRNS", &[0; 2]),
+            chunk("IDAT", b""),
+            chunk("IEND", b""),
+        ]);
+        assert_eq!(errors(&png), vec![Some(1)]); // Not whole entries (tRNS is then checked against 256 entries)
+        let png = Png::from_chunks(vec![ihdr(0), chunk("tRNS", &[0; 6]), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&png), vec![Some(1)]); // Truecolour layout for a greyscale image
+    }
+
+    #[test]
+    fn test_ancillary_ordering() {
+        let png = Png::from_chunks(vec![
+            ihdr(3),
+            chunk("tRNS", &[0]),
+            chunk("PLTE", &[0; 3]),
+            chunk("gAMA", &[0; 4]),
+            chunk("IDAT", b""),
+            chunk("pHYs", &[0; 9]),
+            chunk("IEND", b""),
+        ]);
+        assert_eq!(errors(&png), vec![Some(1), Some(3), Some(5)]);
+    }
+
+    #[test]
+    fn test_colour_type_rules() {
+        let png = Png::from_chunks(vec![ihdr(6), chunk("tRNS", &[0; 2]), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&png), vec![Some(1)]); // No tRNS with an alpha channel
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&png), vec![Some(0)]); // Zero width
+    }
+
+    #[test]
+    fn test_unknown_critical_chunk() {
+        let png = Png::from_chunks(vec![ihdr(0), chunk("RuSt", b""), chunk("IDAT", b""), chunk("IEND", b"")]);
+        assert_eq!(errors(&png), vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_reserved_bit_is_warning() {
+        let png = Png::from_chunks(vec![ihdr(0), chunk("IDAT", b""), chunk("ruse", b""), chunk("IEND", b"")]);
+        let issues = png.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert!(png.is_conforming());
+    }
+}
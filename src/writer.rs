@@ -0,0 +1,182 @@
+use crate::chunk::{crc_of, Chunk};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::utils::{Error, Limit, Result};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Streaming png serialiser: writes the signature, then one chunk at a time, computing length and CRC on the fly
+pub struct PngWriter<W: Write> {
+    writer: W,
+    iend_written: bool,
+}
+
+impl<W: Write> PngWriter<W> {
+    /// Writes the png signature
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER).map_err(|_| Error::FileError)?;
+        Ok(Self {
+            writer,
+            iend_written: false,
+        })
+    }
+
+    /// Writes a chunk, with its stored CRC if it has one (see `Chunk::output_crc`)
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        self.write_parts(chunk.chunk_type(), chunk.data(), chunk.output_crc())
+    }
+
+    /// Writes a chunk from its type and data, without building a `Chunk` first
+    pub fn write_raw(&mut self, chunk_type: &ChunkType, data: &[u8]) -> Result<()> {
+        self.write_parts(chunk_type, data, crc_of(chunk_type, data))
+    }
+
+    fn write_parts(&mut self, chunk_type: &ChunkType, data: &[u8], crc: u32) -> Result<()> {
+        let length = u32::try_from(data.len())
+            .ok()
+            .filter(|&length| length <= Chunk::MAX_LENGTH)
+            .ok_or(Error::LimitExceeded {
+                limit: Limit::ChunkSize,
+                max: Chunk::MAX_LENGTH as usize,
+            })?;
+        for part in [&length.to_be_bytes()[..], &chunk_type.bytes(), data, &crc.to_be_bytes()] {
+            self.writer.write_all(part).map_err(|_| Error::FileError)?;
+        }
+        if chunk_type.bytes() == *b"IEND" {
+            self.iend_written = true;
+        }
+        Ok(())
+    }
+
+    /// Writes raw trailing bytes, which only make sense after IEND
+    pub fn write_trailer(&mut self, trailer: &[u8]) -> Result<()> {
+        if !self.iend_written {
+            return Err(Error::MissingIend);
+        }
+        self.writer.write_all(trailer).map_err(|_| Error::FileError)
+    }
+
+    /// Flushes and gives the inner writer back, failing if no IEND chunk was written
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush().map_err(|_| Error::FileError)?;
+        if !self.iend_written {
+            return Err(Error::MissingIend);
+        }
+        Ok(self.writer)
+    }
+}
+
+impl Png {
+    /// Serialises the png into `writer` chunk by chunk (see `PngWriter`), instead of collecting it like `as_bytes`
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<W> {
+        let mut writer = PngWriter::new(writer)?;
+        for chunk in self.chunks() {
+            writer.write_chunk(chunk)?;
+        }
+        if !self.trailer().is_empty() {
+            writer.write_trailer(self.trailer())?;
+        }
+        writer.finish()
+    }
+
+    /// Writes the png chunk by chunk into a temporary file next to `path`, synced then renamed over it,
+    /// so that a crash or a full disk never leaves a truncated png behind (optionally keeping the original as `<path>.bak`).
+    /// The new file gets the permissions of the one it replaces
+    pub fn save(&self, path: &Path, backup: bool) -> Result<()> {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(path.file_name().ok_or(Error::FileError)?);
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_name); // Same directory, hence same file system (rename is atomic)
+
+        let written = self.write_synced(&temp_path).and_then(|_| {
+            if let Ok(metadata) = fs::metadata(path) {
+                fs::set_permissions(&temp_path, metadata.permissions()).map_err(|_| Error::FileError)?;
+            }
+            if backup && path.exists() {
+                let mut backup_path = path.as_os_str().to_owned();
+                backup_path.push(".bak");
+                fs::copy(path, &backup_path).map_err(|_| Error::FileError)?;
+                File::open(&backup_path).and_then(|file| file.sync_all()).map_err(|_| Error::FileError)?; // On disk before the original goes
+            }
+            fs::rename(&temp_path, path).map_err(|_| Error::FileError)
+        });
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path); // Best effort, the original is untouched anyway
+            return written;
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let _ = File::open(dir).and_then(|dir| dir.sync_all()); // Persists the rename, where directories can be synced
+        }
+        Ok(())
+    }
+
+    fn write_synced(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|_| Error::FileError)?;
+        let file = self
+            .write_to(BufWriter::new(file))?
+            .into_inner()
+            .map_err(|_| Error::FileError)?;
+        file.sync_all().map_err(|_| Error::FileError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_write_matches_as_bytes() {
+        let png = Png::from_chunks(vec![chunk("FrSt", "I am the first chunk"), chunk("IEND", "")]);
+        let written = png.write_to(Vec::new()).unwrap();
+        assert_eq!(written, png.as_bytes());
+    }
+
+    #[test]
+    fn test_finish_without_iend() {
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer.write_chunk(&chunk("FrSt", "I am the first chunk")).unwrap();
+        assert!(matches!(writer.finish(), Err(Error::MissingIend)));
+    }
+
+    #[test]
+    fn test_save_with_backup() {
+        let dir = std::env::temp_dir().join(format!("pngsct-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let png = Png::from_chunks(vec![chunk("IEND", "")]);
+        png.save(&path, true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+        assert_eq!(fs::read(dir.join("image.png.bak")).unwrap(), b"original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2); // No temporary file left
+
+        let incomplete = Png::from_chunks(Vec::new()); // No IEND, so writing fails
+        assert!(incomplete.save(&path, false).is_err());
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_keeps_permissions() {
+        let dir = std::env::temp_dir().join(format!("pngsct-permissions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.png");
+        fs::write(&path, b"original").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        Png::from_chunks(vec![chunk("IEND", "")]).save(&path, false).unwrap();
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
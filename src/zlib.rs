@@ -0,0 +1,410 @@
+use crate::utils::{Error, Limit, Result};
+
+// Minimal zlib (RFC 1950) codec over deflate (RFC 1951): the decompressor handles every block type, the compressor
+// finds repeated strings (LZ77) and writes them with the fixed Huffman codes, which is plenty for textual metadata
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 128; // Match candidates tried per position, trading ratio for speed
+
+fn invalid(why: &str) -> Error {
+    Error::InvalidData(format!("zlib stream {why}"))
+}
+
+/// Adler-32 checksum of the uncompressed data, closing every zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        // Largest run which cannot overflow before the modulo
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompresses a zlib stream, failing with `LimitExceeded` as soon as the output would exceed `max_length` bytes
+/// (so that a tiny hostile stream cannot expand into gigabytes)
+pub fn decompress(data: &[u8], max_length: usize) -> Result<Vec<u8>> {
+    let (&cmf, &flg) = data.first().zip(data.get(1)).ok_or(invalid("is truncated"))?;
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(invalid("has an invalid header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("needs a preset dictionary"));
+    }
+
+    let mut reader = BitReader::new(&data[2..]);
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let length = reader.bits(16)?;
+                if reader.bits(16)? != !length & 0xFFFF {
+                    return Err(invalid("has a corrupted stored block"));
+                }
+                for _ in 0..length {
+                    push(&mut output, reader.bits(8)? as u8, max_length)?;
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances, max_length)?;
+            }
+            _ => return Err(invalid("has an invalid block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    reader.align();
+    let checksum = (0..4).try_fold(0u32, |checksum, _| Ok::<_, Error>(checksum << 8 | reader.bits(8)?))?;
+    if checksum != adler32(&output) {
+        return Err(invalid("has a wrong checksum"));
+    }
+    Ok(output)
+}
+
+fn push(output: &mut Vec<u8>, byte: u8, max_length: usize) -> Result<()> {
+    if output.len() >= max_length {
+        return Err(Error::LimitExceeded {
+            limit: Limit::Decompression,
+            max: max_length,
+        });
+    }
+    output.push(byte);
+    Ok(())
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    max_length: usize,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => push(output, symbol as u8, max_length)?,
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                let length = LENGTH_BASES[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = distances.decode(reader)? as usize;
+                if d >= DISTANCE_BASES.len() {
+                    return Err(invalid("has an invalid distance code"));
+                }
+                let distance = DISTANCE_BASES[d] as usize + reader.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(invalid("refers to data before its start"));
+                }
+                for _ in 0..length {
+                    push(output, output[output.len() - distance], max_length)?; // Byte by byte, as copies may overlap
+                }
+            }
+            _ => return Err(invalid("has an invalid length code")),
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&[5; 30]).unwrap()) // Both complete by definition
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code_lengths.decode(reader)? {
+            length @ 0..=15 => (length as u8, 1),
+            16 => {
+                let previous = *i.checked_sub(1).map(|p| &lengths[p]).ok_or(invalid("repeats a missing length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        let run = lengths.get_mut(i..i + repeat).ok_or(invalid("has too many code lengths"))?;
+        run.fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("has no end of block code"));
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+/// Least significant bit first reader over the deflate stream
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32, // Bits left in `buffer`, always less than 8 between reads
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.position).ok_or(invalid("is truncated"))?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],  // Number of codes of each length
+    symbols: Vec<u16>, // Symbols ordered by code
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("has an over-subscribed code"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate().filter(|(_, &length)| length != 0) {
+            symbols[offsets[length as usize] as usize] = symbol as u16;
+            offsets[length as usize] += 1;
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("has an invalid Huffman code"))
+    }
+}
+
+/// Least significant bit first writer, Huffman codes being written most significant bit first
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buffer |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn copy(&mut self, length: usize, distance: usize) {
+        let l = LENGTH_BASES.iter().rposition(|&base| base as usize <= length).unwrap();
+        self.literal(257 + l as u32);
+        self.bits((length - LENGTH_BASES[l] as usize) as u32, LENGTH_EXTRA[l] as u32);
+        let d = DISTANCE_BASES.iter().rposition(|&base| base as usize <= distance).unwrap();
+        self.code(d as u32, 5);
+        self.bits((distance - DISTANCE_BASES[d] as usize) as u32, DISTANCE_EXTRA[d] as u32);
+    }
+}
+
+/// Compresses into a zlib stream (a single fixed Huffman block)
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        output: vec![0x78, 0x9C], // Deflate, 32K window, default level
+        buffer: 0,
+        count: 0,
+    };
+    writer.bits(1, 1); // Last block
+    writer.bits(1, 2); // Fixed Huffman codes
+
+    // Positions of each 3-byte sequence, chained from the most recent one
+    let hash = |i: usize| ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & (WINDOW - 1);
+    let mut head = vec![usize::MAX; WINDOW];
+    let mut previous = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if i + 3 <= data.len() {
+            let mut candidate = head[hash(i)];
+            let mut tries = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && tries < MAX_CHAIN {
+                let max = MAX_MATCH.min(data.len() - i);
+                let length = (0..max).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, i - candidate);
+                }
+                candidate = previous[candidate];
+                tries += 1;
+            }
+        }
+        if best_length >= 3 {
+            writer.copy(best_length, best_distance);
+            for k in i..i + best_length {
+                insert(k, &mut head, &mut previous);
+            }
+            i += best_length;
+        } else {
+            writer.literal(data[i] as u32);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    writer.literal(256);
+    writer.bits(0, (8 - writer.count) % 8); // Pads the last partial byte
+    let mut output = writer.output;
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "Long payloads stored as plain chunks bloat the file, long payloads compress well. ".repeat(50);
+        for data in [&b""[..], b"a", b"aaaaaaaaaaaaaaaaaaaaaa", text.as_bytes()] {
+            let compressed = compress(data);
+            assert_eq!(decompress(&compressed, usize::MAX).unwrap(), data);
+        }
+        assert!(compress(text.as_bytes()).len() < text.len() / 10);
+    }
+
+    #[test]
+    fn test_foreign_streams() {
+        // zlib.compress(b"hello hello hello hello") (fixed Huffman) and a stored block
+        let fixed = [120, 156, 203, 72, 205, 201, 201, 87, 200, 64, 39, 1, 104, 3, 8, 177];
+        assert_eq!(decompress(&fixed, 100).unwrap(), b"hello hello hello hello");
+        let stored = [120, 1, 1, 3, 0, 252, 255, 97, 98, 99, 2, 77, 1, 39];
+        assert_eq!(decompress(&stored, 100).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_dynamic_block() {
+        // zlib.compress(text, 9), which picks dynamic Huffman codes
+        let text = "Portable Network Graphics is a raster graphics file format that supports lossless data compression. \
+                    PNG was developed as an improved, non-patented replacement for GIF.";
+        let compressed = [
+            120, 218, 53, 141, 209, 13, 131, 64, 12, 67, 87, 241, 0, 45, 99, 20, 245, 7, 177, 66, 10, 1, 78, 189, 187, 68,
+            73, 10, 235, 55, 253, 232, 143, 165, 103, 91, 246, 44, 22, 244, 170, 140, 137, 227, 18, 123, 99, 52, 210, 163,
+            44, 142, 226, 32, 24, 121, 176, 97, 255, 155, 91, 201, 234, 38, 214, 40, 16, 71, 138, 127, 84, 115, 194, 81,
+            197, 189, 178, 59, 86, 10, 194, 34, 77, 45, 169, 72, 31, 48, 79, 35, 46, 202, 132, 79, 174, 162, 188, 34, 129,
+            58, 74, 118, 228, 228, 245, 134, 46, 253, 174, 20, 220, 35, 67, 99, 173, 180, 112, 75, 250, 93, 97, 124, 62,
+            134, 47, 102, 49, 61, 117,
+        ];
+        assert_eq!(compressed[2] >> 1 & 0b11, 2); // Dynamic Huffman block
+        assert_eq!(decompress(&compressed, usize::MAX).unwrap(), text.as_bytes());
+    }
+
+    #[test]
+    fn test_bomb_and_damage() {
+        let zeros = compress(&vec![0; 100_000]);
+        assert!(zeros.len() < 1000);
+        assert!(matches!(
+            decompress(&zeros, 10_000),
+            Err(Error::LimitExceeded { limit: Limit::Decompression, max: 10_000 })
+        ));
+        let mut damaged = compress(b"some text to damage");
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        assert!(decompress(&damaged, usize::MAX).is_err());
+        assert!(decompress(&damaged[..5], usize::MAX).is_err());
+    }
+}